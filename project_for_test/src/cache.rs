@@ -0,0 +1,120 @@
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+// Каталог и предельный размер кэша ответов getTransaction
+const TX_CACHE_DIR: &str = "tx_cache";
+const TX_CACHE_MAX_BYTES: u64 = 512 * 1024 * 1024;
+const TX_CACHE_ZSTD_LEVEL: i32 = 3;
+// Записи сначала пишутся во временный файл и атомарно переименовываются
+const TX_CACHE_TMP_EXTENSION: &str = "tmp";
+
+// Дисковый кэш ответов getTransaction (подпись + кодировка -> result, сжатый zstd).
+// При превышении размера удаляются самые старые записи
+#[derive(Clone)]
+pub struct TxCache {
+    index: Arc<Mutex<CacheIndex>>,
+}
+
+struct CacheIndex {
+    entries: VecDeque<(PathBuf, u64)>,
+    total_bytes: u64,
+}
+
+impl TxCache {
+    // Открывает кэш и восстанавливает индекс по уже лежащим на диске файлам
+    pub fn open() -> Self {
+        if let Err(e) = fs::create_dir_all(TX_CACHE_DIR) {
+            println!("Ошибка создания каталога кэша {}: {:?}", TX_CACHE_DIR, e);
+        }
+
+        let mut files: Vec<(SystemTime, PathBuf, u64)> = fs::read_dir(TX_CACHE_DIR)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                if !metadata.is_file() {
+                    return None;
+                }
+                // Недописанный временный файл остался от падения — он не нужен
+                if entry.path().extension().map_or(false, |ext| ext == TX_CACHE_TMP_EXTENSION) {
+                    let _ = fs::remove_file(entry.path());
+                    return None;
+                }
+                Some((metadata.modified().ok()?, entry.path(), metadata.len()))
+            })
+            .collect();
+        files.sort();
+
+        let total_bytes = files.iter().map(|(_, _, size)| size).sum();
+        let entries = files.into_iter().map(|(_, path, size)| (path, size)).collect();
+        println!("Кэш транзакций: {} байт в {}", total_bytes, TX_CACHE_DIR);
+
+        TxCache { index: Arc::new(Mutex::new(CacheIndex { entries, total_bytes })) }
+    }
+
+    // Битая запись удаляется, чтобы следующий `put` мог её перезаписать
+    pub fn get(&self, signature: &str, encoding: &str) -> Option<Value> {
+        let path = entry_path(signature, encoding);
+        let compressed = fs::read(&path).ok()?;
+        let decoded = zstd::decode_all(&compressed[..]).ok().and_then(|raw| serde_json::from_slice(&raw).ok());
+
+        if decoded.is_none() {
+            println!("Битая запись кэша {}, удаляем", path.display());
+            self.evict(&path);
+        }
+        decoded
+    }
+
+    pub fn put(&self, signature: &str, encoding: &str, result: &Value) {
+        let path = entry_path(signature, encoding);
+        let raw = serde_json::to_vec(result).expect("Ошибка сериализации ответа");
+        let compressed = match zstd::encode_all(&raw[..], TX_CACHE_ZSTD_LEVEL) {
+            Ok(compressed) => compressed,
+            Err(e) => {
+                println!("Ошибка сжатия ответа {}: {:?}", signature, e);
+                return;
+            }
+        };
+
+        // Проверка и запись под блокировкой индекса, иначе параллельные `put` посчитают файл дважды
+        let mut index = self.index.lock().unwrap();
+        if path.exists() {
+            return;
+        }
+
+        let tmp_path = path.with_extension(TX_CACHE_TMP_EXTENSION);
+        if let Err(e) = fs::write(&tmp_path, &compressed).and_then(|_| fs::rename(&tmp_path, &path)) {
+            println!("Ошибка записи в кэш {}: {:?}", path.display(), e);
+            let _ = fs::remove_file(&tmp_path);
+            return;
+        }
+
+        index.total_bytes += compressed.len() as u64;
+        index.entries.push_back((path, compressed.len() as u64));
+
+        while index.total_bytes > TX_CACHE_MAX_BYTES {
+            let Some((oldest, size)) = index.entries.pop_front() else { break };
+            let _ = fs::remove_file(&oldest);
+            index.total_bytes -= size;
+        }
+    }
+
+    fn evict(&self, path: &Path) {
+        let mut index = self.index.lock().unwrap();
+        if let Some(position) = index.entries.iter().position(|(entry, _)| entry.as_path() == path) {
+            if let Some((_, size)) = index.entries.remove(position) {
+                index.total_bytes -= size;
+            }
+        }
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn entry_path(signature: &str, encoding: &str) -> PathBuf {
+    PathBuf::from(TX_CACHE_DIR).join(format!("{}.{}.json.zst", signature, encoding))
+}
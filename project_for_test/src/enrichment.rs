@@ -0,0 +1,88 @@
+use anyhow::anyhow;
+use serde_json::Value;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::price::now_unix;
+
+// Что делать, если внешний поиск для обогащения события упал или завис
+#[derive(Clone, Copy, PartialEq)]
+pub enum EnrichmentMode {
+    // Ждать обогащения с повторами, задерживая конвейер (для тех, кому важна полнота)
+    Block,
+    // Сразу писать событие с пустыми полями и дозаполнять его через events_corrections
+    Degrade,
+}
+
+// Значения по умолчанию для ENRICHMENT_MODE (block|degrade), ENRICHMENT_TIMEOUT_MS и ENRICHMENT_MAX_ATTEMPTS
+const DEFAULT_ENRICHMENT_MODE: EnrichmentMode = EnrichmentMode::Degrade;
+const DEFAULT_ENRICHMENT_TIMEOUT_MS: u64 = 2000;
+const DEFAULT_ENRICHMENT_MAX_ATTEMPTS: u32 = 8;
+// Пауза между повторами
+pub const ENRICHMENT_RETRY_SECS: u64 = 15;
+const CORRECTIONS_FILE: &str = "events_corrections.json";
+
+// Настройки обогащения: режим, таймаут одной попытки и число попыток до отказа
+pub struct EnrichmentConfig {
+    pub mode: EnrichmentMode,
+    pub timeout_ms: u64,
+    pub max_attempts: u32,
+}
+
+static CONFIG: OnceLock<EnrichmentConfig> = OnceLock::new();
+
+// Читается из окружения (и .env) при первом обращении; некорректные значения заменяются умолчаниями
+pub fn config() -> &'static EnrichmentConfig {
+    CONFIG.get_or_init(|| {
+        let mode = match std::env::var("ENRICHMENT_MODE").as_deref() {
+            Ok("block") => EnrichmentMode::Block,
+            Ok("degrade") => EnrichmentMode::Degrade,
+            Ok(other) => {
+                println!("Неизвестный ENRICHMENT_MODE {}, используется degrade", other);
+                DEFAULT_ENRICHMENT_MODE
+            }
+            Err(_) => DEFAULT_ENRICHMENT_MODE,
+        };
+
+        EnrichmentConfig {
+            mode,
+            timeout_ms: env_or("ENRICHMENT_TIMEOUT_MS", DEFAULT_ENRICHMENT_TIMEOUT_MS),
+            max_attempts: env_or("ENRICHMENT_MAX_ATTEMPTS", DEFAULT_ENRICHMENT_MAX_ATTEMPTS),
+        }
+    })
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            println!("Некорректное значение {}={}, используется значение по умолчанию", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+// Одна попытка обогащения с таймаутом
+pub async fn with_timeout<T>(lookup: impl Future<Output = anyhow::Result<T>>) -> anyhow::Result<T> {
+    let timeout_ms = config().timeout_ms;
+    tokio::time::timeout(Duration::from_millis(timeout_ms), lookup)
+        .await
+        .map_err(|_| anyhow!("таймаут {} мс", timeout_ms))?
+}
+
+// Сохранение исправления ранее записанного события в events_corrections.json
+pub fn save_correction(event: &str, key: Value, patch: Value) {
+    let correction = serde_json::json!({
+        "event": event,
+        "key": key,
+        "patch": patch,
+        "corrected_at": now_unix()
+    });
+
+    let mut file = OpenOptions::new().create(true).append(true).open(CORRECTIONS_FILE).expect("Ошибка открытия файла");
+    writeln!(file, "{}", correction).expect("Ошибка записи в файл");
+}
@@ -0,0 +1,411 @@
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, NaiveDate};
+use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::Arc;
+
+use crate::finality::STATUS_FILE;
+use crate::SWAP_EVENTS_FILE;
+
+pub const USAGE: &str = "Использование: export [--pool <id>] [--from <дата>] [--to <дата>] [--commitment confirmed|finalized|orphaned] \
+     [--format parquet|csv|ndjson] [--out <файл>]\n\
+     Даты: YYYY-MM-DD (--to включительно) или RFC 3339";
+
+const CSV_HEADER: &str = "transaction_signature,slot,block_time,pool,amount_in,min_amount_out,commitment";
+
+const PARQUET_SCHEMA: &str = "
+    message swap_event {
+        REQUIRED BYTE_ARRAY transaction_signature (UTF8);
+        REQUIRED INT64 slot (INTEGER(64, false));
+        OPTIONAL INT64 block_time;
+        OPTIONAL BYTE_ARRAY pool (UTF8);
+        REQUIRED INT64 amount_in (INTEGER(64, false));
+        REQUIRED INT64 min_amount_out (INTEGER(64, false));
+        OPTIONAL BYTE_ARRAY commitment (UTF8);
+    }
+";
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Parquet,
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Parquet => "parquet",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+// Фильтры выгрузки; границы времени — по block_time, `to` не включается.
+// commitment сравнивается с актуальным статусом события из swap_event_status.json
+struct ExportQuery {
+    pool: Option<String>,
+    from: Option<u64>,
+    to: Option<u64>,
+    commitment: Option<String>,
+    format: ExportFormat,
+    out: String,
+}
+
+// Почему событие не попало в выгрузку. Отсутствие поля считается отдельно от несовпадения,
+// чтобы события старых версий без pool или block_time не пропадали молча
+#[derive(Debug, PartialEq)]
+enum Skip {
+    Mismatch,
+    NoPool,
+    NoBlockTime,
+}
+
+// Строка swap_events.json в том виде, в каком она выгружается
+struct SwapRow {
+    transaction_signature: String,
+    slot: u64,
+    block_time: Option<u64>,
+    pool: Option<String>,
+    amount_in: u64,
+    min_amount_out: u64,
+    commitment: Option<String>,
+}
+
+impl SwapRow {
+    fn from_json(event: &Value) -> Option<Self> {
+        Some(SwapRow {
+            transaction_signature: event["transaction_signature"].as_str()?.to_string(),
+            slot: event["slot"].as_u64()?,
+            block_time: event["block_time"].as_u64(),
+            pool: event["pool"].as_str().map(|p| p.to_string()),
+            amount_in: event["amount_in"].as_u64()?,
+            min_amount_out: event["min_amount_out"].as_u64()?,
+            commitment: event["commitment"].as_str().map(|c| c.to_string()),
+        })
+    }
+}
+
+// Подкоманда `export`: фильтрует события из приёмника и пишет их в выбранном формате
+pub fn run_export(args: &[String]) -> anyhow::Result<()> {
+    let query = parse_args(args)?;
+    let rows = read_rows(&query)?;
+
+    match query.format {
+        ExportFormat::Parquet => write_parquet(&query.out, &rows)?,
+        ExportFormat::Csv => write_csv(&query.out, &rows)?,
+        ExportFormat::Ndjson => write_ndjson(&query.out, &rows)?,
+    }
+
+    println!("Выгружено {} событий в {}", rows.len(), query.out);
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> anyhow::Result<ExportQuery> {
+    let mut pool = None;
+    let mut from = None;
+    let mut to = None;
+    let mut commitment = None;
+    let mut format = ExportFormat::Ndjson;
+    let mut out = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| anyhow!("не указано значение для {}", flag))?;
+        match flag.as_str() {
+            "--pool" => pool = Some(value.clone()),
+            "--from" => from = Some(parse_time(value, false)?),
+            "--to" => to = Some(parse_time(value, true)?),
+            "--commitment" => {
+                commitment = match value.as_str() {
+                    "confirmed" | "finalized" | "orphaned" => Some(value.clone()),
+                    other => bail!("неизвестный commitment {}", other),
+                }
+            }
+            "--format" => {
+                format = match value.as_str() {
+                    "parquet" => ExportFormat::Parquet,
+                    "csv" => ExportFormat::Csv,
+                    "ndjson" => ExportFormat::Ndjson,
+                    other => bail!("неизвестный формат {}", other),
+                }
+            }
+            "--out" => out = Some(value.clone()),
+            other => bail!("неизвестный флаг {}", other),
+        }
+    }
+
+    let out = out.unwrap_or_else(|| format!("export.{}", format.extension()));
+    Ok(ExportQuery { pool, from, to, commitment, format, out })
+}
+
+// Дата без времени как верхняя граница означает конец этого дня
+fn parse_time(value: &str, end_of_day: bool) -> anyhow::Result<u64> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let date = if end_of_day { date.succ_opt().context("дата вне диапазона")? } else { date };
+        let timestamp = date.and_hms_opt(0, 0, 0).context("некорректная дата")?.and_utc().timestamp();
+        return u64::try_from(timestamp).context("дата раньше 1970 года");
+    }
+
+    let timestamp = DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("не удалось разобрать дату {}", value))?
+        .timestamp();
+    u64::try_from(timestamp).context("дата раньше 1970 года")
+}
+
+// Последний записанный статус каждого события (файла может не быть, если finality ещё ничего не повысил)
+fn read_statuses() -> anyhow::Result<HashMap<String, String>> {
+    let mut statuses = HashMap::new();
+    let file = match File::open(STATUS_FILE) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(statuses),
+        Err(e) => return Err(e).with_context(|| format!("не удалось открыть {}", STATUS_FILE)),
+    };
+
+    for line in BufReader::new(file).lines() {
+        let Ok(status) = serde_json::from_str::<Value>(&line?) else { continue };
+        if let (Some(signature), Some(commitment)) = (status["transaction_signature"].as_str(), status["commitment"].as_str()) {
+            statuses.insert(signature.to_string(), commitment.to_string());
+        }
+    }
+    Ok(statuses)
+}
+
+fn read_rows(query: &ExportQuery) -> anyhow::Result<Vec<SwapRow>> {
+    let statuses = read_statuses()?;
+    let file = File::open(SWAP_EVENTS_FILE).with_context(|| format!("не удалось открыть {}", SWAP_EVENTS_FILE))?;
+    let mut rows = Vec::new();
+    let (mut no_pool, mut no_block_time) = (0, 0);
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let Some(mut row) = serde_json::from_str::<Value>(&line).ok().as_ref().and_then(SwapRow::from_json) else {
+            continue;
+        };
+        if let Some(commitment) = statuses.get(&row.transaction_signature) {
+            row.commitment = Some(commitment.clone());
+        }
+
+        match check_row(query, &row) {
+            Ok(()) => rows.push(row),
+            Err(Skip::NoPool) => no_pool += 1,
+            Err(Skip::NoBlockTime) => no_block_time += 1,
+            Err(Skip::Mismatch) => {}
+        }
+    }
+
+    if no_pool > 0 {
+        println!("Пропущено {} событий без pool: фильтр --pool к ним неприменим", no_pool);
+    }
+    if no_block_time > 0 {
+        println!("Пропущено {} событий без block_time: фильтр по времени к ним неприменим", no_block_time);
+    }
+    Ok(rows)
+}
+
+// События без pool или block_time (записанные старыми версиями) под соответствующий фильтр не попадают
+fn check_row(query: &ExportQuery, row: &SwapRow) -> Result<(), Skip> {
+    if let Some(pool) = &query.pool {
+        match &row.pool {
+            None => return Err(Skip::NoPool),
+            Some(row_pool) if row_pool != pool => return Err(Skip::Mismatch),
+            Some(_) => {}
+        }
+    }
+    if query.from.is_some() || query.to.is_some() {
+        let block_time = row.block_time.ok_or(Skip::NoBlockTime)?;
+        if query.from.map_or(false, |from| block_time < from) || query.to.map_or(false, |to| block_time >= to) {
+            return Err(Skip::Mismatch);
+        }
+    }
+    if query.commitment.is_some() && row.commitment != query.commitment {
+        return Err(Skip::Mismatch);
+    }
+    Ok(())
+}
+
+fn write_ndjson(out: &str, rows: &[SwapRow]) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(out)?);
+    for row in rows {
+        let event = serde_json::json!({
+            "transaction_signature": row.transaction_signature,
+            "slot": row.slot,
+            "block_time": row.block_time,
+            "pool": row.pool,
+            "amount_in": row.amount_in,
+            "min_amount_out": row.min_amount_out,
+            "commitment": row.commitment
+        });
+        writeln!(writer, "{}", event)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_csv(out: &str, rows: &[SwapRow]) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(out)?);
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            row.transaction_signature,
+            row.slot,
+            row.block_time.map(|t| t.to_string()).unwrap_or_default(),
+            row.pool.as_deref().unwrap_or(""),
+            row.amount_in,
+            row.min_amount_out,
+            row.commitment.as_deref().unwrap_or("")
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_parquet(out: &str, rows: &[SwapRow]) -> anyhow::Result<()> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(File::create(out)?, schema, props)?;
+    let mut row_group = writer.next_row_group()?;
+
+    let mut column_index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match column_index {
+            0 => write_strings(&mut column, rows.iter().map(|r| Some(r.transaction_signature.as_str())), false)?,
+            1 => write_ints(&mut column, rows.iter().map(|r| Some(r.slot)), false)?,
+            2 => write_ints(&mut column, rows.iter().map(|r| r.block_time), true)?,
+            3 => write_strings(&mut column, rows.iter().map(|r| r.pool.as_deref()), true)?,
+            4 => write_ints(&mut column, rows.iter().map(|r| Some(r.amount_in)), false)?,
+            5 => write_ints(&mut column, rows.iter().map(|r| Some(r.min_amount_out)), false)?,
+            6 => write_strings(&mut column, rows.iter().map(|r| r.commitment.as_deref()), true)?,
+            _ => bail!("лишняя колонка в схеме parquet"),
+        }
+        column.close()?;
+        column_index += 1;
+    }
+
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+// Для OPTIONAL-колонок пишутся уровни определения: 1 — значение есть, 0 — null
+fn definition_levels<T>(values: &[Option<T>]) -> Vec<i16> {
+    values.iter().map(|v| v.is_some() as i16).collect()
+}
+
+fn write_ints(
+    column: &mut parquet::file::writer::SerializedColumnWriter<'_>,
+    values: impl Iterator<Item = Option<u64>>,
+    optional: bool,
+) -> anyhow::Result<()> {
+    let values: Vec<Option<u64>> = values.collect();
+    let present: Vec<i64> = values.iter().flatten().map(|&v| v as i64).collect();
+    let levels = definition_levels(&values);
+
+    column.typed::<Int64Type>().write_batch(&present, optional.then_some(&levels[..]), None)?;
+    Ok(())
+}
+
+fn write_strings<'a>(
+    column: &mut parquet::file::writer::SerializedColumnWriter<'_>,
+    values: impl Iterator<Item = Option<&'a str>>,
+    optional: bool,
+) -> anyhow::Result<()> {
+    let values: Vec<Option<&str>> = values.collect();
+    let present: Vec<ByteArray> = values.iter().flatten().map(|&v| ByteArray::from(v)).collect();
+    let levels = definition_levels(&values);
+
+    column.typed::<ByteArrayType>().write_batch(&present, optional.then_some(&levels[..]), None)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-05-01T00:00:00Z и 2024-05-01T12:00:00Z
+    const MAY_1: u64 = 1714521600;
+    const MAY_1_NOON: u64 = 1714564800;
+    const DAY_SECS: u64 = 86400;
+
+    fn query(from: Option<u64>, to: Option<u64>) -> ExportQuery {
+        ExportQuery { pool: None, from, to, commitment: None, format: ExportFormat::Ndjson, out: String::new() }
+    }
+
+    fn row(block_time: Option<u64>, pool: Option<&str>) -> SwapRow {
+        SwapRow {
+            transaction_signature: "sig".to_string(),
+            slot: 1,
+            block_time,
+            pool: pool.map(|p| p.to_string()),
+            amount_in: 10,
+            min_amount_out: 5,
+            commitment: Some("confirmed".to_string()),
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn commitment_filter_rejects_unknown_values() {
+        let query = parse_args(&args(&["--commitment", "finalized"])).unwrap();
+        assert_eq!(query.commitment.as_deref(), Some("finalized"));
+        assert!(parse_args(&args(&["--commitment", "finalised"])).is_err());
+    }
+
+    #[test]
+    fn date_bounds_cover_whole_days() {
+        assert_eq!(parse_time("2024-05-01", false).unwrap(), MAY_1);
+        assert_eq!(parse_time("2024-05-01", true).unwrap(), MAY_1 + DAY_SECS);
+    }
+
+    #[test]
+    fn rfc3339_bounds_are_exact() {
+        assert_eq!(parse_time("2024-05-01T12:00:00Z", false).unwrap(), MAY_1_NOON);
+        assert_eq!(parse_time("2024-05-01T12:00:00Z", true).unwrap(), MAY_1_NOON);
+        assert_eq!(parse_time("2024-05-01T15:00:00+03:00", true).unwrap(), MAY_1_NOON);
+    }
+
+    #[test]
+    fn date_upper_bound_includes_the_last_second_of_the_day() {
+        let query = query(None, Some(parse_time("2024-05-01", true).unwrap()));
+
+        assert_eq!(check_row(&query, &row(Some(MAY_1 + DAY_SECS - 1), None)), Ok(()));
+        assert_eq!(check_row(&query, &row(Some(MAY_1 + DAY_SECS), None)), Err(Skip::Mismatch));
+    }
+
+    #[test]
+    fn rfc3339_upper_bound_is_exclusive() {
+        let query = query(None, Some(parse_time("2024-05-01T12:00:00Z", true).unwrap()));
+
+        assert_eq!(check_row(&query, &row(Some(MAY_1_NOON - 1), None)), Ok(()));
+        assert_eq!(check_row(&query, &row(Some(MAY_1_NOON), None)), Err(Skip::Mismatch));
+    }
+
+    #[test]
+    fn rows_without_block_time_are_reported_only_under_a_time_filter() {
+        assert_eq!(check_row(&query(None, None), &row(None, None)), Ok(()));
+        assert_eq!(check_row(&query(Some(MAY_1), None), &row(None, None)), Err(Skip::NoBlockTime));
+        assert_eq!(check_row(&query(None, Some(MAY_1)), &row(None, None)), Err(Skip::NoBlockTime));
+    }
+
+    #[test]
+    fn rows_without_pool_are_reported_under_a_pool_filter() {
+        let mut query = query(None, None);
+        query.pool = Some("pool".to_string());
+
+        assert_eq!(check_row(&query, &row(None, Some("pool"))), Ok(()));
+        assert_eq!(check_row(&query, &row(None, Some("other"))), Err(Skip::Mismatch));
+        assert_eq!(check_row(&query, &row(None, None)), Err(Skip::NoPool));
+    }
+}
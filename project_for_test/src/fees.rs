@@ -0,0 +1,205 @@
+use serde_json::Value;
+use solana_program::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::price::now_unix;
+use crate::watchdog::Heartbeats;
+
+const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+// Тег инструкции SetComputeUnitPrice (u64 микролампортов за CU)
+const SET_COMPUTE_UNIT_PRICE_TAG: u8 = 3;
+
+// Скользящее окно статистики
+const FEE_WINDOW_SECS: u64 = 300;
+const FEE_WINDOW_MAX_SAMPLES: usize = 10_000;
+
+// Как часто писать снимок статистики в поток событий
+const FEE_STATS_EMIT_SECS: u64 = 30;
+const FEE_STATS_FILE: &str = "fee_stats.json";
+
+// Цена CU из SetComputeUnitPrice; без этой инструкции приоритетная комиссия нулевая
+pub fn compute_unit_price(msg: &VersionedMessage) -> u64 {
+    let compute_budget = Pubkey::from_str(COMPUTE_BUDGET_PROGRAM_ID).unwrap();
+    let account_keys = msg.static_account_keys();
+
+    msg.instructions()
+        .iter()
+        .filter(|cix| account_keys.get(cix.program_id_index as usize) == Some(&compute_budget))
+        .find_map(|cix| match cix.data.split_first() {
+            Some((&SET_COMPUTE_UNIT_PRICE_TAG, rest)) => Some(u64::from_le_bytes(rest.get(..8)?.try_into().ok()?)),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+// Окно наблюдений (время получения, цена CU)
+#[derive(Default)]
+struct FeeWindow {
+    samples: VecDeque<(u64, u64)>,
+}
+
+impl FeeWindow {
+    fn push(&mut self, timestamp: u64, price: u64) {
+        self.samples.push_back((timestamp, price));
+        if self.samples.len() > FEE_WINDOW_MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    fn prune(&mut self, now: u64) {
+        while let Some(&(timestamp, _)) = self.samples.front() {
+            if now.saturating_sub(timestamp) <= FEE_WINDOW_SECS {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut prices: Vec<u64> = self.samples.iter().map(|&(_, price)| price).collect();
+        prices.sort_unstable();
+
+        serde_json::json!({
+            "samples": prices.len(),
+            "window_secs": FEE_WINDOW_SECS,
+            "p25": percentile(&prices, 25),
+            "p50": percentile(&prices, 50),
+            "p90": percentile(&prices, 90)
+        })
+    }
+}
+
+fn percentile(sorted: &[u64], p: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    Some(sorted[(sorted.len() - 1) * p / 100])
+}
+
+// Статистика цен CU по успешным свапам Raydium: глобально и по пулам
+#[derive(Clone, Default)]
+pub struct FeeStats {
+    inner: Arc<Mutex<FeeStatsInner>>,
+}
+
+#[derive(Default)]
+struct FeeStatsInner {
+    global: FeeWindow,
+    pools: HashMap<String, FeeWindow>,
+}
+
+impl FeeStats {
+    // Одна транзакция учитывается в глобальном окне один раз и в окне каждого затронутого пула;
+    // pools не должен содержать повторов
+    pub fn record(&self, pools: &[String], price: u64) {
+        let now = now_unix();
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.global.push(now, price);
+        for pool in pools {
+            inner.pools.entry(pool.clone()).or_default().push(now, price);
+        }
+    }
+
+    pub fn global_json(&self) -> Value {
+        let mut inner = self.inner.lock().unwrap();
+        inner.global.prune(now_unix());
+        inner.global.to_json()
+    }
+
+    pub fn pool_json(&self, pool: &str) -> Option<Value> {
+        let mut inner = self.inner.lock().unwrap();
+        let window = inner.pools.get_mut(pool)?;
+        window.prune(now_unix());
+        Some(window.to_json())
+    }
+
+    // Снимок всех окон; пустые окна пулов заодно удаляются
+    fn snapshot_json(&self) -> Value {
+        let now = now_unix();
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.global.prune(now);
+        inner.pools.retain(|_, window| {
+            window.prune(now);
+            !window.samples.is_empty()
+        });
+
+        let pools: serde_json::Map<String, Value> =
+            inner.pools.iter().map(|(pool, window)| (pool.clone(), window.to_json())).collect();
+
+        serde_json::json!({
+            "event": "PriorityFeeStats",
+            "timestamp": now,
+            "global": inner.global.to_json(),
+            "pools": pools
+        })
+    }
+}
+
+// Периодически пишет снимок статистики комиссий в fee_stats.json
+pub async fn emit_fee_stats(fee_stats: FeeStats, heartbeats: Heartbeats) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(FEE_STATS_EMIT_SECS)).await;
+
+        let event = fee_stats.snapshot_json();
+        let mut file = OpenOptions::new().create(true).append(true).open(FEE_STATS_FILE).expect("Ошибка открытия файла");
+        writeln!(file, "{}", event).expect("Ошибка записи в файл");
+        heartbeats.beat("fee_stats");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::message::Message;
+
+    fn message(instructions: &[Instruction]) -> VersionedMessage {
+        VersionedMessage::Legacy(Message::new(instructions, Some(&Pubkey::new_unique())))
+    }
+
+    fn compute_budget_ix(data: Vec<u8>) -> Instruction {
+        Instruction::new_with_bytes(Pubkey::from_str(COMPUTE_BUDGET_PROGRAM_ID).unwrap(), &data, vec![])
+    }
+
+    #[test]
+    fn percentile_of_empty_window_is_none() {
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn percentile_rounds_index_down() {
+        let sorted: Vec<u64> = (1..=10).collect();
+        // Индекс (len - 1) * p / 100: 9 * 25 / 100 = 2, 9 * 50 / 100 = 4, 9 * 90 / 100 = 8
+        assert_eq!(percentile(&sorted, 0), Some(1));
+        assert_eq!(percentile(&sorted, 25), Some(3));
+        assert_eq!(percentile(&sorted, 50), Some(5));
+        assert_eq!(percentile(&sorted, 90), Some(9));
+        assert_eq!(percentile(&sorted, 100), Some(10));
+        assert_eq!(percentile(&[7], 90), Some(7));
+    }
+
+    #[test]
+    fn compute_unit_price_is_read_from_set_compute_unit_price() {
+        let mut data = vec![SET_COMPUTE_UNIT_PRICE_TAG];
+        data.extend_from_slice(&25_000u64.to_le_bytes());
+        // SetComputeUnitLimit (тег 2) перед ценой не должен мешать
+        let limit = compute_budget_ix(vec![2, 0x40, 0x0d, 0x03, 0x00]);
+
+        assert_eq!(compute_unit_price(&message(&[limit, compute_budget_ix(data)])), 25_000);
+    }
+
+    #[test]
+    fn compute_unit_price_defaults_to_zero() {
+        assert_eq!(compute_unit_price(&message(&[])), 0);
+        // Обрезанные данные инструкции не считаются ценой
+        assert_eq!(compute_unit_price(&message(&[compute_budget_ix(vec![SET_COMPUTE_UNIT_PRICE_TAG, 1, 2])])), 0);
+    }
+}
@@ -0,0 +1,214 @@
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::price::now_unix;
+use crate::watchdog::Heartbeats;
+use crate::RPC_HTTP_URL;
+
+// Как часто проверять статусы и сколько подписей за запрос (лимит getSignatureStatuses)
+const FINALITY_POLL_SECS: u64 = 10;
+const FINALITY_BATCH_SIZE: usize = 256;
+// Через сколько секунд исчезнувшая транзакция считается осиротевшей
+const ORPHAN_AFTER_SECS: u64 = 120;
+// Последний commitment каждого события; `export` подставляет его вместо захваченного
+pub const STATUS_FILE: &str = "swap_event_status.json";
+// Неразрешённые подписи переживают перезапуск процесса
+const PENDING_FILE: &str = "finality_pending.json";
+
+// Подписи событий, ещё не дошедших до finalized
+#[derive(Clone, Default)]
+pub struct FinalityTracker {
+    pending: Arc<Mutex<PendingQueue>>,
+}
+
+// Подписи проверяются по кругу, чтобы при большом хвосте до каждой доходила очередь.
+// В `order` могут оставаться уже разрешённые подписи — они выбрасываются при обходе
+#[derive(Default)]
+struct PendingQueue {
+    order: VecDeque<String>,
+    events: HashMap<String, PendingEvent>,
+}
+
+struct PendingEvent {
+    slot: u64,
+    first_seen: u64,
+}
+
+impl FinalityTracker {
+    // Восстанавливает подписи, не разрешённые до прошлой остановки
+    pub fn load() -> Self {
+        let tracker = FinalityTracker::default();
+        let Some(saved) = fs::read_to_string(PENDING_FILE).ok().and_then(|raw| serde_json::from_str::<Value>(&raw).ok()) else {
+            return tracker;
+        };
+
+        {
+            let mut queue = tracker.pending.lock().unwrap();
+            for event in saved.as_array().into_iter().flatten() {
+                let (Some(signature), Some(slot), Some(first_seen)) =
+                    (event["transaction_signature"].as_str(), event["slot"].as_u64(), event["first_seen"].as_u64())
+                else {
+                    continue;
+                };
+                if queue.events.insert(signature.to_string(), PendingEvent { slot, first_seen }).is_none() {
+                    queue.order.push_back(signature.to_string());
+                }
+            }
+            println!("Восстановлено {} событий, ожидающих finalized", queue.events.len());
+        }
+        tracker
+    }
+
+    pub fn track(&self, signature: &str, slot: u64) {
+        let mut queue = self.pending.lock().unwrap();
+        if !queue.events.contains_key(signature) {
+            queue.events.insert(signature.to_string(), PendingEvent { slot, first_seen: now_unix() });
+            queue.order.push_back(signature.to_string());
+        }
+    }
+
+    // Берёт до FINALITY_BATCH_SIZE подписей из головы очереди и переносит их в хвост
+    fn batch(&self) -> Vec<String> {
+        let mut queue = self.pending.lock().unwrap();
+        let mut batch = Vec::new();
+
+        for _ in 0..queue.order.len() {
+            if batch.len() == FINALITY_BATCH_SIZE {
+                break;
+            }
+            let Some(signature) = queue.order.pop_front() else { break };
+            if queue.events.contains_key(&signature) {
+                batch.push(signature.clone());
+                queue.order.push_back(signature);
+            }
+        }
+        batch
+    }
+
+    fn resolve(&self, signature: &str) -> Option<PendingEvent> {
+        self.pending.lock().unwrap().events.remove(signature)
+    }
+
+    fn first_seen(&self, signature: &str) -> Option<u64> {
+        self.pending.lock().unwrap().events.get(signature).map(|p| p.first_seen)
+    }
+
+    // Сохраняет неразрешённые подписи; вызывается каждый цикл и перед выходом из программы
+    pub fn persist(&self) {
+        let saved: Vec<Value> = {
+            let queue = self.pending.lock().unwrap();
+            queue
+                .order
+                .iter()
+                .filter_map(|signature| {
+                    let event = queue.events.get(signature)?;
+                    Some(serde_json::json!({
+                        "transaction_signature": signature,
+                        "slot": event.slot,
+                        "first_seen": event.first_seen
+                    }))
+                })
+                .collect()
+        };
+
+        // Через временный файл, чтобы не оставить обрезанное состояние
+        let tmp_path = format!("{}.tmp", PENDING_FILE);
+        if let Err(e) = fs::write(&tmp_path, Value::from(saved).to_string()).and_then(|_| fs::rename(&tmp_path, PENDING_FILE)) {
+            println!("Ошибка сохранения {}: {:?}", PENDING_FILE, e);
+        }
+    }
+}
+
+// Повышает события до finalized или помечает осиротевшими, записывая статус в swap_event_status.json
+pub async fn run_finality_updater(tracker: FinalityTracker, heartbeats: Heartbeats) {
+    let client = Client::new();
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(FINALITY_POLL_SECS)).await;
+        heartbeats.beat("finality");
+
+        let signatures = tracker.batch();
+        if signatures.is_empty() {
+            continue;
+        }
+
+        let statuses = match fetch_signature_statuses(&client, &signatures, false).await {
+            Some(statuses) => statuses,
+            None => {
+                println!("Не удалось получить статусы {} подписей", signatures.len());
+                continue;
+            }
+        };
+
+        let mut expired = Vec::new();
+        for (signature, status) in signatures.iter().zip(statuses.iter()) {
+            if is_finalized(status) {
+                resolve(&tracker, signature, "finalized");
+            } else if status.is_null() && tracker.first_seen(signature).map_or(false, |t| now_unix().saturating_sub(t) > ORPHAN_AFTER_SECS) {
+                expired.push(signature.clone());
+            }
+        }
+
+        // Без поиска по истории подпись пропадает из кэша статусов и после финализации,
+        // поэтому осиротевшей считается только та, которой нет и в истории
+        if !expired.is_empty() {
+            match fetch_signature_statuses(&client, &expired, true).await {
+                Some(statuses) => {
+                    for (signature, status) in expired.iter().zip(statuses.iter()) {
+                        if is_finalized(status) {
+                            resolve(&tracker, signature, "finalized");
+                        } else if status.is_null() {
+                            resolve(&tracker, signature, "orphaned");
+                        }
+                    }
+                }
+                None => println!("Не удалось перепроверить {} подписей по истории", expired.len()),
+            }
+        }
+
+        tracker.persist();
+    }
+}
+
+fn is_finalized(status: &Value) -> bool {
+    status["confirmationStatus"].as_str() == Some("finalized")
+}
+
+fn resolve(tracker: &FinalityTracker, signature: &str, commitment: &str) {
+    if let Some(event) = tracker.resolve(signature) {
+        println!("Событие {} -> {}", signature, commitment);
+        save_status(signature, event.slot, commitment);
+    }
+}
+
+async fn fetch_signature_statuses(client: &Client, signatures: &[String], search_history: bool) -> Option<Vec<Value>> {
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getSignatureStatuses",
+        "params": [ signatures, { "searchTransactionHistory": search_history } ]
+    });
+
+    let response = client.post(RPC_HTTP_URL).json(&request_body).send().await.ok()?;
+    let json_resp: Value = response.json().await.ok()?;
+
+    json_resp["result"]["value"].as_array().cloned()
+}
+
+// Сохранение обновлённого commitment события в JSON
+fn save_status(signature: &str, slot: u64, commitment: &str) {
+    let event = serde_json::json!({
+        "transaction_signature": signature,
+        "slot": slot,
+        "commitment": commitment,
+        "updated_at": now_unix()
+    });
+
+    let mut file = OpenOptions::new().create(true).append(true).open(STATUS_FILE).expect("Ошибка открытия файла");
+    writeln!(file, "{}", event).expect("Ошибка записи в файл");
+}
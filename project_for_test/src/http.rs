@@ -0,0 +1,168 @@
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::price::{is_quote_mint, now_unix, PoolPrice};
+use crate::AppContext;
+
+// Адрес локального HTTP-сервера
+const HTTP_BIND_ADDR: &str = "127.0.0.1:8080";
+// Клиенты с этим Accept получают бинарные кадры вместо JSON
+const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+// Поток цен: JSON-строки или подряд идущие кадры фиксированной длины
+const PRICE_STREAM_PATH: &str = "/stream/prices";
+
+// Формат ответа, выбранный клиентом
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Json,
+    Binary,
+}
+
+enum Body {
+    Json(Value),
+    Binary(Vec<u8>),
+}
+
+// Локальный HTTP-сервер для внутренних запросов (цены пулов, статистика комиссий)
+pub async fn run_http_server(ctx: AppContext) -> std::io::Result<()> {
+    let listener = TcpListener::bind(HTTP_BIND_ADDR).await?;
+    println!("HTTP-сервер слушает {}", HTTP_BIND_ADDR);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &ctx).await {
+                println!("Ошибка HTTP-соединения: {:?}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream, ctx: &AppContext) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    // Из заголовков нужен только Accept для выбора формата
+    let mut encoding = Encoding::Json;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("accept") && value.contains(BINARY_CONTENT_TYPE) {
+                encoding = Encoding::Binary;
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    if method == "GET" && (path == PRICE_STREAM_PATH || path.starts_with(&format!("{}/", PRICE_STREAM_PATH))) {
+        let pool = path.strip_prefix(PRICE_STREAM_PATH).and_then(|rest| rest.strip_prefix('/'));
+        return stream_prices(writer, encoding, pool, ctx).await;
+    }
+
+    let (status, body) = route(method, path, encoding, ctx);
+    let (content_type, body) = match body {
+        Body::Json(value) => ("application/json", value.to_string().into_bytes()),
+        Body::Binary(frame) => (BINARY_CONTENT_TYPE, frame),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, content_type, body.len()
+    );
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(&body).await?;
+    writer.shutdown().await
+}
+
+// Держит соединение открытым и пишет каждое обновление цены (всех пулов или одного).
+// Бинарные кадры идут подряд без разделителей, JSON — по строке на обновление
+async fn stream_prices(
+    mut writer: tokio::net::tcp::OwnedWriteHalf,
+    encoding: Encoding,
+    pool: Option<&str>,
+    ctx: &AppContext,
+) -> std::io::Result<()> {
+    let mut updates = ctx.price_state.subscribe();
+    let content_type = match encoding {
+        Encoding::Json => "application/x-ndjson",
+        Encoding::Binary => BINARY_CONTENT_TYPE,
+    };
+    let head = format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\nConnection: close\r\n\r\n", content_type);
+    writer.write_all(head.as_bytes()).await?;
+
+    loop {
+        let price: PoolPrice = match updates.recv().await {
+            Ok(price) => price,
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                println!("Подписчик потока цен отстал, пропущено {} обновлений", skipped);
+                continue;
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return writer.shutdown().await,
+        };
+
+        if pool.map_or(false, |pool| pool != price.pool) {
+            continue;
+        }
+
+        let payload = match encoding {
+            Encoding::Json => format!("{}\n", price.to_json(now_unix())).into_bytes(),
+            Encoding::Binary => match price.to_frame(now_unix()) {
+                Some(frame) => frame,
+                None => {
+                    println!("Не удалось закодировать цену пула {} в кадр", price.pool);
+                    continue;
+                }
+            },
+        };
+        writer.write_all(&payload).await?;
+    }
+}
+
+// Ошибки всегда отдаются в JSON, бинарный формат только для успешных ответов
+fn route(method: &str, path: &str, encoding: Encoding, ctx: &AppContext) -> (&'static str, Body) {
+    if method != "GET" {
+        return ("405 Method Not Allowed", Body::Json(serde_json::json!({ "error": "method not allowed" })));
+    }
+
+    if let Some(mint_or_pool) = path.strip_prefix("/price/") {
+        if is_quote_mint(mint_or_pool) {
+            return ("400 Bad Request", Body::Json(serde_json::json!({ "error": "quote mint is ambiguous, query by pool", "id": mint_or_pool })));
+        }
+        return match ctx.price_state.lookup(mint_or_pool) {
+            Some(price) if encoding == Encoding::Binary => match price.to_frame(now_unix()) {
+                Some(frame) => ("200 OK", Body::Binary(frame)),
+                None => ("500 Internal Server Error", Body::Json(serde_json::json!({ "error": "price cannot be encoded as a frame", "id": mint_or_pool }))),
+            },
+            Some(price) => ("200 OK", Body::Json(price.to_json(now_unix()))),
+            None => ("404 Not Found", Body::Json(serde_json::json!({ "error": "unknown pool or mint", "id": mint_or_pool }))),
+        };
+    }
+
+    if path == "/stats/fees" {
+        return ("200 OK", Body::Json(ctx.fee_stats.global_json()));
+    }
+
+    if let Some(pool) = path.strip_prefix("/stats/fees/") {
+        return match ctx.fee_stats.pool_json(pool) {
+            Some(stats) => ("200 OK", Body::Json(stats)),
+            None => ("404 Not Found", Body::Json(serde_json::json!({ "error": "unknown pool", "id": pool }))),
+        };
+    }
+
+    if path == "/stats/watchdog" {
+        return ("200 OK", Body::Json(ctx.heartbeats.to_json()));
+    }
+
+    ("404 Not Found", Body::Json(serde_json::json!({ "error": "not found" })))
+}
@@ -0,0 +1,505 @@
+use anyhow::{bail, Context};
+use reqwest::Client;
+use serde_json::Value;
+use solana_program::message::VersionedMessage;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::enrichment::{self, EnrichmentMode, ENRICHMENT_RETRY_SECS};
+use crate::price::{now_unix, QUOTE_MINTS};
+use crate::watchdog::Heartbeats;
+use crate::{full_account_keys, RAYDIUM_PROGRAM_ID, RPC_HTTP_URL};
+
+const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+// Теги InitializeMint и InitializeMint2
+const INITIALIZE_MINT_TAGS: [u8; 2] = [0, 20];
+
+// Минт, чья история начинается раньше пула больше чем на это время, не считается новым.
+// Graduated-токены к созданию пула уже имеют тысячи транзакций, поэтому решает возраст, а не длина истории
+const MINT_NEW_MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+// История минта листается страницами getSignaturesForAddress, но не дальше MINT_HISTORY_MAX_PAGES
+const MINT_HISTORY_PAGE_SIZE: usize = 1000;
+const MINT_HISTORY_MAX_PAGES: usize = 50;
+const LISTINGS_FILE: &str = "token_listings.json";
+// Очередь дозаполнения переживает перезапуск процесса
+const PENDING_PATCHES_FILE: &str = "enrichment_pending.json";
+// Сколько помнить увиденные создания минтов и пулов, так и не дошедшие до листинга
+const CREATION_TTL_SECS: u64 = 24 * 60 * 60;
+
+// Аккаунт пула AmmInfo: размер и смещения coin_mint и pc_mint
+const AMM_INFO_SIZE: u64 = 752;
+const AMM_INFO_COIN_MINT_OFFSET: u64 = 400;
+const AMM_INFO_PC_MINT_OFFSET: u64 = 432;
+
+// Создание минта: где и когда он появился
+#[derive(Clone)]
+pub struct MintCreation {
+    pub signature: String,
+    pub slot: u64,
+    pub timestamp: Option<u64>,
+}
+
+// Пул Raydium, созданный инструкцией Initialize2
+pub struct PoolCreation {
+    pub pool: String,
+    pub coin_mint: String,
+    pub pc_mint: String,
+    pub signature: String,
+    pub slot: u64,
+    pub timestamp: u64,
+}
+
+impl PoolCreation {
+    // Аккаунты Initialize2: amm — 4-й, coin_mint — 8-й, pc_mint — 9-й
+    pub fn from_initialize2(cix: &CompiledInstruction, account_keys: &[Pubkey], signature: &str, slot: u64, timestamp: u64) -> Option<Self> {
+        let key = |position: usize| account_keys.get(*cix.accounts.get(position)? as usize).map(|k| k.to_string());
+
+        Some(PoolCreation {
+            pool: key(4)?,
+            coin_mint: key(8)?,
+            pc_mint: key(9)?,
+            signature: signature.to_string(),
+            slot,
+            timestamp,
+        })
+    }
+}
+
+// Связывает новые минты с первым пулом Raydium, созданным для них
+#[derive(Clone, Default)]
+pub struct LineageTracker {
+    inner: Arc<Mutex<LineageInner>>,
+}
+
+#[derive(Default)]
+struct LineageInner {
+    created: HashMap<String, MintCreation>,
+    // Минты, уже залистившиеся (восстанавливаются из token_listings.json)
+    listed: HashSet<String>,
+    // Минты, оказавшиеся старыми к моменту создания пула: повторно их историю не запрашиваем
+    old_mints: HashSet<String>,
+    // Пулы, чьё создание мы видели: пул -> (слот, время)
+    pool_creations: HashMap<String, (u64, u64)>,
+    pending: VecDeque<PendingPatch>,
+}
+
+// Листинг, записанный без данных о создании минта или без проверки на более ранний пул
+#[derive(Clone)]
+struct PendingPatch {
+    mint: String,
+    pool: String,
+    pool_slot: u64,
+    pool_timestamp: u64,
+    attempts: u32,
+    needs_creation: bool,
+    needs_pool_check: bool,
+}
+
+impl PendingPatch {
+    fn to_json(&self) -> Value {
+        serde_json::json!({
+            "mint": self.mint,
+            "pool": self.pool,
+            "pool_slot": self.pool_slot,
+            "pool_timestamp": self.pool_timestamp,
+            "attempts": self.attempts,
+            "needs_creation": self.needs_creation,
+            "needs_pool_check": self.needs_pool_check
+        })
+    }
+
+    fn from_json(patch: &Value) -> Option<Self> {
+        Some(PendingPatch {
+            mint: patch["mint"].as_str()?.to_string(),
+            pool: patch["pool"].as_str()?.to_string(),
+            pool_slot: patch["pool_slot"].as_u64()?,
+            pool_timestamp: patch["pool_timestamp"].as_u64()?,
+            attempts: patch["attempts"].as_u64()? as u32,
+            needs_creation: patch["needs_creation"].as_bool()?,
+            needs_pool_check: patch["needs_pool_check"].as_bool()?,
+        })
+    }
+}
+
+impl LineageTracker {
+    // Восстанавливает уже записанные листинги и очередь их дозаполнения; котируемые минты заведомо старые
+    pub fn load() -> Self {
+        let tracker = LineageTracker::default();
+        {
+            let mut inner = tracker.inner.lock().unwrap();
+            inner.old_mints.extend(QUOTE_MINTS.iter().map(|mint| mint.to_string()));
+
+            if let Ok(file) = File::open(LISTINGS_FILE) {
+                for line in BufReader::new(file).lines().map_while(Result::ok) {
+                    let Ok(listing) = serde_json::from_str::<Value>(&line) else { continue };
+                    if let Some(mint) = listing["mint"].as_str() {
+                        inner.listed.insert(mint.to_string());
+                    }
+                }
+            }
+            if let Some(saved) = fs::read_to_string(PENDING_PATCHES_FILE).ok().and_then(|raw| serde_json::from_str::<Value>(&raw).ok()) {
+                inner.pending.extend(saved.as_array().into_iter().flatten().filter_map(PendingPatch::from_json));
+            }
+            println!("Восстановлено {} залистившихся минтов, {} ждут дозаполнения", inner.listed.len(), inner.pending.len());
+        }
+        tracker
+    }
+
+    // Запоминает минты, созданные в транзакции (на верхнем уровне и во внутренних инструкциях)
+    pub fn observe_mint_creations(&self, msg: &VersionedMessage, meta: &Value, signature: &str, slot: u64, timestamp: u64) {
+        let account_keys = full_account_keys(msg, meta);
+        let token_programs = [
+            Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            Pubkey::from_str(TOKEN_2022_PROGRAM_ID).unwrap(),
+        ];
+
+        let mut instructions: Vec<(u8, Vec<u8>, Vec<u8>)> = msg
+            .instructions()
+            .iter()
+            .map(|cix| (cix.program_id_index, cix.accounts.clone(), cix.data.clone()))
+            .collect();
+
+        for inner in meta["innerInstructions"].as_array().into_iter().flatten() {
+            for ix in inner["instructions"].as_array().into_iter().flatten() {
+                let (Some(program_id_index), Some(accounts), Some(data)) =
+                    (ix["programIdIndex"].as_u64(), ix["accounts"].as_array(), ix["data"].as_str())
+                else {
+                    continue;
+                };
+                let accounts = accounts.iter().filter_map(|a| a.as_u64()).map(|a| a as u8).collect();
+                let Ok(data) = bs58::decode(data).into_vec() else { continue };
+                instructions.push((program_id_index as u8, accounts, data));
+            }
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        for (program_id_index, accounts, data) in instructions {
+            let is_token_program = account_keys.get(program_id_index as usize).map_or(false, |p| token_programs.contains(p));
+            if !is_token_program || !data.first().map_or(false, |tag| INITIALIZE_MINT_TAGS.contains(tag)) {
+                continue;
+            }
+
+            if let Some(mint) = accounts.first().and_then(|&i| account_keys.get(i as usize)) {
+                println!("Создан минт {} в слоте {}", mint, slot);
+                inner.created.entry(mint.to_string()).or_insert(MintCreation {
+                    signature: signature.to_string(),
+                    slot,
+                    timestamp: Some(timestamp),
+                });
+            }
+        }
+    }
+
+    // Для каждого нового минта пула, ещё не листившегося на Raydium, пишет TokenListedOnRaydium.
+    // Запускается отдельной задачей: в режиме Block повторы поиска могут занять минуты
+    pub async fn on_pool_created(&self, pool: &PoolCreation) {
+        self.inner.lock().unwrap().pool_creations.insert(pool.pool.clone(), (pool.slot, pool.timestamp));
+
+        for (mint, quote_mint) in [(&pool.coin_mint, &pool.pc_mint), (&pool.pc_mint, &pool.coin_mint)] {
+            let known = {
+                let inner = self.inner.lock().unwrap();
+                if inner.listed.contains(mint) || inner.old_mints.contains(mint) {
+                    continue;
+                }
+                inner.created.get(mint).cloned()
+            };
+
+            // Листинг первый, только если на Raydium нет более раннего пула с этим минтом.
+            // Если проверка не удалась, листинг пишется непроверенным и перепроверяется в run_patch_up
+            let pool_check_complete = match self.earlier_pool(mint, &pool.pool, pool.slot).await {
+                Ok(Some(earlier_pool)) => {
+                    println!("Минт {} уже торгуется на Raydium в пуле {}, листинг не первый", mint, earlier_pool);
+                    self.inner.lock().unwrap().listed.insert(mint.clone());
+                    continue;
+                }
+                Ok(None) => true,
+                Err(e) => {
+                    println!("Не удалось проверить пулы минта {}: {:#}, листинг пишется непроверенным", mint, e);
+                    false
+                }
+            };
+
+            let creation = match known {
+                Some(creation) => Some(creation),
+                None => match resolve_mint_creation(mint, pool.timestamp).await {
+                    Ok(Some(creation)) => Some(creation),
+                    Ok(None) => {
+                        self.inner.lock().unwrap().old_mints.insert(mint.clone());
+                        continue;
+                    }
+                    Err(e) => {
+                        println!("Не удалось узнать создание минта {}: {:#}, листинг пишется без него", mint, e);
+                        None
+                    }
+                },
+            };
+
+            let first_listing = {
+                let mut inner = self.inner.lock().unwrap();
+                inner.created.remove(mint);
+                let first_listing = inner.listed.insert(mint.clone());
+                if first_listing && (creation.is_none() || !pool_check_complete) {
+                    inner.pending.push_back(PendingPatch {
+                        mint: mint.clone(),
+                        pool: pool.pool.clone(),
+                        pool_slot: pool.slot,
+                        pool_timestamp: pool.timestamp,
+                        attempts: 0,
+                        needs_creation: creation.is_none(),
+                        needs_pool_check: !pool_check_complete,
+                    });
+                }
+                first_listing
+            };
+
+            if first_listing {
+                save_listing(mint, quote_mint, creation.as_ref(), pool_check_complete, pool);
+                if creation.is_none() || !pool_check_complete {
+                    self.persist_pending();
+                }
+            }
+        }
+    }
+
+    // Дозаполняет листинги без данных о минте и пишет исправления в events_corrections.
+    // Патч снимается с головы очереди только после обработки, поэтому перезапуск стадии его не теряет
+    pub async fn run_patch_up(self, heartbeats: Heartbeats) {
+        loop {
+            tokio::time::sleep(Duration::from_secs(ENRICHMENT_RETRY_SECS)).await;
+            heartbeats.beat("enrichment_patch");
+            self.prune_creations();
+
+            let queued = self.inner.lock().unwrap().pending.len();
+            for _ in 0..queued {
+                let Some(mut patch) = self.inner.lock().unwrap().pending.front().cloned() else { break };
+
+                let outcome = self.patch_listing(&mut patch).await;
+                heartbeats.beat("enrichment_patch");
+                self.inner.lock().unwrap().pending.pop_front();
+
+                if let Err(e) = outcome {
+                    patch.attempts += 1;
+                    if patch.attempts < enrichment::config().max_attempts {
+                        self.inner.lock().unwrap().pending.push_back(patch);
+                    } else {
+                        println!("Дозаполнение минта {} прекращено после {} попыток: {:#}", patch.mint, patch.attempts, e);
+                        let mut failed = serde_json::json!({});
+                        if patch.needs_creation {
+                            failed["enrichment"] = Value::from("failed");
+                        }
+                        if patch.needs_pool_check {
+                            failed["first_pool_check"] = Value::from("failed");
+                        }
+                        let key = serde_json::json!({ "mint": patch.mint, "pool": patch.pool });
+                        enrichment::save_correction("TokenListedOnRaydium", key, failed);
+                    }
+                }
+            }
+
+            self.persist_pending();
+        }
+    }
+
+    // Сохраняет очередь дозаполнения; вызывается при её изменении и перед выходом из программы
+    pub fn persist_pending(&self) {
+        let saved: Vec<Value> = self.inner.lock().unwrap().pending.iter().map(PendingPatch::to_json).collect();
+
+        // Через временный файл, чтобы не оставить обрезанное состояние
+        let tmp_path = format!("{}.tmp", PENDING_PATCHES_FILE);
+        if let Err(e) = fs::write(&tmp_path, Value::from(saved).to_string()).and_then(|_| fs::rename(&tmp_path, PENDING_PATCHES_FILE)) {
+            println!("Ошибка сохранения {}: {:?}", PENDING_PATCHES_FILE, e);
+        }
+    }
+
+    // Один проход дозаполнения листинга. Err — что-то ещё не удалось узнать, патч остаётся в очереди
+    async fn patch_listing(&self, patch: &mut PendingPatch) -> anyhow::Result<()> {
+        let key = serde_json::json!({ "mint": patch.mint, "pool": patch.pool });
+
+        if patch.needs_pool_check {
+            // Нашёлся более ранний пул — листинг был ложным
+            if let Some(earlier_pool) = self.earlier_pool(&patch.mint, &patch.pool, patch.pool_slot).await? {
+                enrichment::save_correction("TokenListedOnRaydium", key, serde_json::json!({ "retracted": true, "earlier_pool": earlier_pool }));
+                return Ok(());
+            }
+            patch.needs_pool_check = false;
+            enrichment::save_correction("TokenListedOnRaydium", key.clone(), serde_json::json!({ "first_pool_check": "complete" }));
+        }
+
+        if patch.needs_creation {
+            match lookup_mint_creation(&patch.mint, patch.pool_timestamp).await? {
+                Some(creation) => {
+                    enrichment::save_correction("TokenListedOnRaydium", key, creation_fields(Some(&creation), patch.pool_timestamp));
+                }
+                // Минт оказался старым — листинг был ложным
+                None => {
+                    self.inner.lock().unwrap().old_mints.insert(patch.mint.clone());
+                    enrichment::save_correction("TokenListedOnRaydium", key, serde_json::json!({ "retracted": true }));
+                }
+            }
+            patch.needs_creation = false;
+        }
+
+        Ok(())
+    }
+
+    // Более ранний пул Raydium с этим минтом; пулы, чьё создание мы видели позже этого, не считаются
+    async fn earlier_pool(&self, mint: &str, pool: &str, pool_slot: u64) -> anyhow::Result<Option<String>> {
+        let pools = enrichment::with_timeout(raydium_pools_with_mint(mint)).await?;
+        let inner = self.inner.lock().unwrap();
+        Ok(pools
+            .into_iter()
+            .find(|other| other != pool && inner.pool_creations.get(other).map_or(true, |&(slot, _)| slot < pool_slot)))
+    }
+
+    // Забывает создания минтов и пулов старше CREATION_TTL_SECS
+    fn prune_creations(&self) {
+        let now = now_unix();
+        let mut inner = self.inner.lock().unwrap();
+        inner.created.retain(|_, creation| creation.timestamp.map_or(false, |t| now.saturating_sub(t) < CREATION_TTL_SECS));
+        inner.pool_creations.retain(|_, &mut (_, timestamp)| now.saturating_sub(timestamp) < CREATION_TTL_SECS);
+    }
+}
+
+// В режиме Block повторяет поиск, пока не получит ответ или не исчерпает попытки
+async fn resolve_mint_creation(mint: &str, pool_timestamp: u64) -> anyhow::Result<Option<MintCreation>> {
+    let mut attempts = 0;
+    loop {
+        match lookup_mint_creation(mint, pool_timestamp).await {
+            Err(e) if enrichment::config().mode == EnrichmentMode::Block && attempts + 1 < enrichment::config().max_attempts => {
+                attempts += 1;
+                println!("Повтор поиска минта {} ({}): {:#}", mint, attempts, e);
+                tokio::time::sleep(Duration::from_secs(ENRICHMENT_RETRY_SECS)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Ищет самую первую транзакцию минта, листая историю от новых подписей к старым (таймаут — на страницу).
+// None — история начинается раньше пула больше чем на MINT_NEW_MAX_AGE_SECS, то есть минт старый
+async fn lookup_mint_creation(mint: &str, pool_timestamp: u64) -> anyhow::Result<Option<MintCreation>> {
+    let client = Client::new();
+    let cutoff = pool_timestamp.saturating_sub(MINT_NEW_MAX_AGE_SECS);
+    let mut oldest: Option<MintCreation> = None;
+
+    for _ in 0..MINT_HISTORY_MAX_PAGES {
+        let before = oldest.as_ref().map(|creation| creation.signature.clone());
+        let page = enrichment::with_timeout(fetch_signatures_page(&client, mint, before.as_deref())).await?;
+
+        // Пустая страница после полной — предыдущая была последней
+        let Some(last) = page.last() else {
+            return oldest.map(Some).context("у минта нет истории подписей");
+        };
+        let creation = MintCreation {
+            signature: last["signature"].as_str().context("в ответе нет signature")?.to_string(),
+            slot: last["slot"].as_u64().context("в ответе нет slot")?,
+            timestamp: last["blockTime"].as_u64(),
+        };
+
+        if creation.timestamp.map_or(false, |t| t < cutoff) {
+            return Ok(None);
+        }
+        if page.len() < MINT_HISTORY_PAGE_SIZE {
+            return Ok(Some(creation));
+        }
+        oldest = Some(creation);
+    }
+
+    bail!("история минта длиннее {} страниц", MINT_HISTORY_MAX_PAGES)
+}
+
+// Одна страница getSignaturesForAddress (от новых к старым), начиная до подписи `before`
+async fn fetch_signatures_page(client: &Client, mint: &str, before: Option<&str>) -> anyhow::Result<Vec<Value>> {
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getSignaturesForAddress",
+        "params": [ mint, { "limit": MINT_HISTORY_PAGE_SIZE, "before": before, "commitment": "confirmed" } ]
+    });
+
+    let response = client.post(RPC_HTTP_URL).json(&request_body).send().await?;
+    let json_resp: Value = response.json().await?;
+    if !json_resp["error"].is_null() {
+        bail!("RPC вернул ошибку: {}", json_resp["error"]);
+    }
+
+    json_resp["result"].as_array().cloned().context("в ответе нет result")
+}
+
+// Пулы Raydium, в которых минт стоит на стороне coin или pc (только адреса, без данных)
+async fn raydium_pools_with_mint(mint: &str) -> anyhow::Result<Vec<String>> {
+    let client = Client::new();
+    let mut pools = Vec::new();
+
+    for offset in [AMM_INFO_COIN_MINT_OFFSET, AMM_INFO_PC_MINT_OFFSET] {
+        let request_body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getProgramAccounts",
+            "params": [
+                RAYDIUM_PROGRAM_ID,
+                {
+                    "encoding": "base64",
+                    "commitment": "confirmed",
+                    "dataSlice": { "offset": 0, "length": 0 },
+                    "filters": [
+                        { "dataSize": AMM_INFO_SIZE },
+                        { "memcmp": { "offset": offset, "bytes": mint } }
+                    ]
+                }
+            ]
+        });
+
+        let response = client.post(RPC_HTTP_URL).json(&request_body).send().await?;
+        let json_resp: Value = response.json().await?;
+        if !json_resp["error"].is_null() {
+            bail!("RPC вернул ошибку: {}", json_resp["error"]);
+        }
+
+        for account in json_resp["result"].as_array().context("в ответе нет result")? {
+            pools.push(account["pubkey"].as_str().context("в ответе нет pubkey")?.to_string());
+        }
+    }
+
+    Ok(pools)
+}
+
+// Поля листинга, зависящие от данных о создании минта (null, пока их нет)
+fn creation_fields(creation: Option<&MintCreation>, pool_timestamp: u64) -> Value {
+    serde_json::json!({
+        "mint_creation_signature": creation.map(|c| c.signature.clone()),
+        "mint_created_slot": creation.map(|c| c.slot),
+        "mint_created_at": creation.and_then(|c| c.timestamp),
+        "time_to_pool_secs": creation.and_then(|c| c.timestamp).map(|t| pool_timestamp.saturating_sub(t)),
+        "enrichment": if creation.is_some() { "complete" } else { "pending" }
+    })
+}
+
+// Сохранение `TokenListedOnRaydium` в JSON
+// first_pool_check = "pending" — не удалось убедиться, что более раннего пула нет
+fn save_listing(mint: &str, quote_mint: &str, creation: Option<&MintCreation>, pool_check_complete: bool, pool: &PoolCreation) {
+    let mut event = serde_json::json!({
+        "event": "TokenListedOnRaydium",
+        "mint": mint,
+        "quote_mint": quote_mint,
+        "pool": pool.pool,
+        "pool_creation_signature": pool.signature,
+        "pool_created_slot": pool.slot,
+        "pool_created_at": pool.timestamp,
+        "first_pool_check": if pool_check_complete { "complete" } else { "pending" }
+    });
+    if let (Some(object), Value::Object(fields)) = (event.as_object_mut(), creation_fields(creation, pool.timestamp)) {
+        object.extend(fields);
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(LISTINGS_FILE).expect("Ошибка открытия файла");
+    writeln!(file, "{}", event).expect("Ошибка записи в файл");
+    println!("Минт {} впервые залистился на Raydium в пуле {}", mint, pool.pool);
+}
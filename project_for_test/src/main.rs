@@ -17,19 +17,153 @@ use carbon_core::instruction::InstructionDecoder;
 use solana_sdk::instruction::Instruction;
 use std::str::FromStr;
 
+mod cache;
+mod enrichment;
+mod export;
+mod fees;
+mod finality;
+mod http;
+mod lineage;
+mod price;
+mod upgrade;
+mod verify;
+mod watchdog;
+
+use cache::TxCache;
+use fees::FeeStats;
+use finality::FinalityTracker;
+use lineage::{LineageTracker, PoolCreation};
+use price::PriceState;
+use verify::{SwapSample, Verifier};
+use watchdog::{Heartbeats, StageExit, Supervisor};
+use std::sync::{Arc, Mutex};
+
 // RPC-эндпоинты
 const RPC_HTTP_URL: &str = "";
 const QUICKNODE_WS_URL: &str = "";
 const RAYDIUM_PROGRAM_ID: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+// Файл-приёмник событий SwapBaseIn (из него же читает `export`)
+const SWAP_EVENTS_FILE: &str = "swap_events.json";
+// Commitment, на котором события захватываются; до finalized их повышает finality
+const CAPTURE_COMMITMENT: &str = "confirmed";
+
+// Сколько секунд без уведомлений считается зависанием подписки
+const WS_STALL_SECS: u64 = 60;
+// То же для фоновых стадий с периодом 30 секунд
+const BACKGROUND_STALL_SECS: u64 = 120;
+
+// Общее состояние конвейера
+#[derive(Clone)]
+struct AppContext {
+    price_state: PriceState,
+    verifier: Verifier,
+    tx_cache: TxCache,
+    fee_stats: FeeStats,
+    finality: FinalityTracker,
+    lineage: LineageTracker,
+    heartbeats: Heartbeats,
+    // Стартовый слот переживает переподключения WebSocket
+    start_slot: Arc<Mutex<Option<u64>>>,
+}
 
 #[tokio::main]
 async fn main() {
-    connect_to_quicknode_ws().await.expect("Ошибка подключения к WebSocket");
+    dotenvy::dotenv().ok();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("export") {
+        if let Err(e) = export::run_export(&args[1..]) {
+            eprintln!("Ошибка экспорта: {:#}", e);
+            eprintln!("{}", export::USAGE);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let enrichment_config = enrichment::config();
+    println!(
+        "Обогащение: режим {}, таймаут {} мс, до {} попыток",
+        if enrichment_config.mode == enrichment::EnrichmentMode::Block { "block" } else { "degrade" },
+        enrichment_config.timeout_ms,
+        enrichment_config.max_attempts
+    );
+
+    let tx_cache = TxCache::open();
+    let ctx = AppContext {
+        price_state: PriceState::default(),
+        verifier: Verifier::default(),
+        tx_cache,
+        fee_stats: FeeStats::default(),
+        finality: FinalityTracker::load(),
+        lineage: LineageTracker::load(),
+        heartbeats: Heartbeats::default(),
+        start_slot: Arc::new(Mutex::new(None)),
+    };
+
+    let mut supervisor = Supervisor::new(ctx.heartbeats.clone());
+
+    let http_ctx = ctx.clone();
+    supervisor.add("http", None, move || {
+        let http_ctx = http_ctx.clone();
+        watchdog::spawn_background(async move {
+            if let Err(e) = http::run_http_server(http_ctx).await {
+                println!("Ошибка HTTP-сервера: {:?}", e);
+            }
+        })
+    });
+
+    if Verifier::is_enabled() {
+        let verify_ctx = ctx.clone();
+        supervisor.add("verifier", None, move || verify_ctx.verifier.spawn_worker(verify_ctx.tx_cache.clone()));
+    }
+
+    let fees_ctx = ctx.clone();
+    supervisor.add("fee_stats", Some(BACKGROUND_STALL_SECS), move || {
+        watchdog::spawn_background(fees::emit_fee_stats(fees_ctx.fee_stats.clone(), fees_ctx.heartbeats.clone()))
+    });
+
+    let finality_ctx = ctx.clone();
+    supervisor.add("finality", Some(BACKGROUND_STALL_SECS), move || {
+        watchdog::spawn_background(finality::run_finality_updater(finality_ctx.finality.clone(), finality_ctx.heartbeats.clone()))
+    });
+
+    let lineage_ctx = ctx.clone();
+    supervisor.add("enrichment_patch", Some(BACKGROUND_STALL_SECS), move || {
+        watchdog::spawn_background(lineage_ctx.lineage.clone().run_patch_up(lineage_ctx.heartbeats.clone()))
+    });
+
+    let upgrade_heartbeats = ctx.heartbeats.clone();
+    supervisor.add("upgrade_watch", Some(BACKGROUND_STALL_SECS), move || {
+        watchdog::spawn_background(upgrade::watch_program_upgrades(upgrade_heartbeats.clone()))
+    });
+
+    let ws_ctx = ctx.clone();
+    supervisor.add("ws", Some(WS_STALL_SECS), move || {
+        let ws_ctx = ws_ctx.clone();
+        tokio::spawn(async move {
+            match connect_to_quicknode_ws(&ws_ctx).await {
+                Ok(()) => StageExit::Finished,
+                Err(e) => {
+                    println!("Ошибка WebSocket: {:?}", e);
+                    StageExit::Failed
+                }
+            }
+        })
+    });
+
+    // Программа завершается, только когда подписка дошла до предела слотов; обрыв связи — перезапуск
+    let exit = supervisor.run_until_finished("ws").await;
+    // Неразрешённые подписи дозавершит следующий запуск
+    ctx.finality.persist();
+    ctx.lineage.persist_pending();
+    if let StageExit::Failed = exit {
+        std::process::exit(1);
+    }
 }
 
-// Подключение к WebSocket Solana и подписка на логи Raydium AMM v4
-async fn connect_to_quicknode_ws() -> Result<(), Box<dyn std::error::Error>> {
-    let (ws_stream, _) = connect_async(QUICKNODE_WS_URL).await.expect("Ошибка подключения к WebSocket");
+// Подключение к WebSocket Solana и подписка на логи Raydium AMM v4.
+// Ok — достигнут предел слотов; ошибка или закрытие потока сервером возвращаются как Err
+async fn connect_to_quicknode_ws(ctx: &AppContext) -> Result<(), Box<dyn std::error::Error>> {
+    let (ws_stream, _) = connect_async(QUICKNODE_WS_URL).await?;
     let (mut write, mut read) = ws_stream.split();
 
     let subscription = serde_json::json!({
@@ -38,14 +172,14 @@ async fn connect_to_quicknode_ws() -> Result<(), Box<dyn std::error::Error>> {
         "method": "logsSubscribe",
         "params": [
             { "mentions": [RAYDIUM_PROGRAM_ID] },
-            { "commitment": "confirmed" }
+            { "commitment": CAPTURE_COMMITMENT }
         ]
     });
 
-    write.send(Message::Text(subscription.to_string())).await.expect("Ошибка отправки подписки");
+    write.send(Message::Text(subscription.to_string())).await?;
     println!("Подписаны на WebSocket QuickNode (Raydium AMM v4)");
 
-    let mut initial_slot: Option<u64> = None;
+    let mut initial_slot: Option<u64> = *ctx.start_slot.lock().unwrap();
 
     while let Some(msg) = read.next().await {
         match msg {
@@ -65,9 +199,11 @@ async fn connect_to_quicknode_ws() -> Result<(), Box<dyn std::error::Error>> {
 
                 let signature = json_resp["params"]["result"]["value"]["signature"].as_str().unwrap_or("").to_string();
                 println!("Новый слот: {}", slot);
+                ctx.heartbeats.beat("ws");
 
                 if initial_slot.is_none() {
                     initial_slot = Some(slot);
+                    *ctx.start_slot.lock().unwrap() = initial_slot;
                     println!("Стартовый слот: {}", slot);
                 }
 
@@ -77,28 +213,54 @@ async fn connect_to_quicknode_ws() -> Result<(), Box<dyn std::error::Error>> {
 
                     if slot_diff >= 100 {
                         println!("Достигнут предел 100 слотов. Останавливаем подписку.");
-                        break;
+                        return Ok(());
                     }
                 }
 
                 println!("Обнаружена транзакция: {}", signature);
-                if let Some(tx) = fetch_transaction(&signature).await {
-                    decode_transaction(&signature, &tx, slot).await;
+                if let Some(fetched) = fetch_transaction(&signature, &ctx.tx_cache).await {
+                    decode_transaction(&signature, &fetched, slot, ctx).await;
                 }
             }
-            Err(e) => {
-                println!("Ошибка WebSocket: {:?}", e);
-                break;
-            }
+            Err(e) => return Err(e.into()),
             _ => {}
         }
     }
 
-    Ok(())
+    Err("WebSocket закрыт сервером".into())
 }
 
-// Запрашивает полную транзакцию
-async fn fetch_transaction(signature: &str) -> Option<VersionedTransaction> {
+// Транзакция вместе с метаданными из getTransaction
+struct FetchedTransaction {
+    tx: VersionedTransaction,
+    meta: Value,
+    block_time: Option<u64>,
+}
+
+// Запрашивает полную транзакцию (сначала из дискового кэша)
+async fn fetch_transaction(signature: &str, tx_cache: &TxCache) -> Option<FetchedTransaction> {
+    let result = match tx_cache.get(signature, "base64") {
+        Some(result) => result,
+        None => {
+            let result = fetch_transaction_rpc(signature).await?;
+            tx_cache.put(signature, "base64", &result);
+            result
+        }
+    };
+
+    let base64_str = result["transaction"][0].as_str()?;
+    let tx_bytes = base64::decode(base64_str).ok()?;
+    let versioned_tx: VersionedTransaction = bincode::deserialize(&tx_bytes).ok()?;
+
+    Some(FetchedTransaction {
+        tx: versioned_tx,
+        meta: result["meta"].clone(),
+        block_time: result["blockTime"].as_u64(),
+    })
+}
+
+// Запрашивает getTransaction у RPC и возвращает поле result
+async fn fetch_transaction_rpc(signature: &str) -> Option<Value> {
     let client = Client::new();
     let request_body = serde_json::json!({
         "jsonrpc": "2.0",
@@ -106,7 +268,7 @@ async fn fetch_transaction(signature: &str) -> Option<VersionedTransaction> {
         "method": "getTransaction",
         "params": [
             signature,
-            { "encoding": "base64", "commitment": "confirmed", "maxSupportedTransactionVersion": 0 }
+            { "encoding": "base64", "commitment": CAPTURE_COMMITMENT, "maxSupportedTransactionVersion": 0 }
         ]
     });
 
@@ -121,35 +283,108 @@ async fn fetch_transaction(signature: &str) -> Option<VersionedTransaction> {
         return None;
     }
 
-    let base64_str = json_resp["result"]["transaction"][0].as_str()?;
-    let tx_bytes = base64::decode(base64_str).ok()?;
-    let versioned_tx: VersionedTransaction = bincode::deserialize(&tx_bytes).ok()?;
-    
-    Some(versioned_tx)
+    Some(json_resp["result"].clone())
 }
 
-// Декодирование транзакции и поиск SwapBaseIn
-async fn decode_transaction(signature: &str, versioned_tx: &VersionedTransaction, slot: u64) {
+// Декодирование транзакции: SwapBaseIn, цены пулов, статистика комиссий и листинги новых минтов
+async fn decode_transaction(signature: &str, fetched: &FetchedTransaction, slot: u64, ctx: &AppContext) {
     let decoder = RaydiumAmmV4Decoder;
+    let versioned_tx = &fetched.tx;
+    // Индексы аккаунтов в инструкциях и meta считаются с учётом lookup-таблиц
+    let account_keys = full_account_keys(&versioned_tx.message, &fetched.meta);
+    let timestamp = fetched.block_time.unwrap_or_else(price::now_unix);
+    let mut swapped_pools: Vec<String> = Vec::new();
+    let mut created_pools: Vec<PoolCreation> = Vec::new();
+    let succeeded = fetched.meta["err"].is_null();
+
+    if succeeded {
+        ctx.lineage.observe_mint_creations(&versioned_tx.message, &fetched.meta, signature, slot, timestamp);
+    }
 
-    for cix in versioned_tx.message.instructions() {
-        if let Some(ix) = convert_compiled_instruction(cix, &versioned_tx.message) {
+    for (instruction_index, cix) in versioned_tx.message.instructions().iter().enumerate() {
+        if let Some(ix) = convert_compiled_instruction(cix, &versioned_tx.message, &account_keys) {
             if let Some(decoded_inst) = decoder.decode_instruction(&ix) {
-                if let RaydiumAmmV4Instruction::SwapBaseIn(swap_data) = decoded_inst.data {
-                    println!("[SwapBaseIn] Signature: {}, amount_in: {}, min_out: {}, slot: {}", signature, swap_data.amount_in, swap_data.minimum_amount_out, slot);
-                    save_event(signature, swap_data.amount_in, swap_data.minimum_amount_out, slot);
+                let pool = cix.accounts.get(1).and_then(|&i| account_keys.get(i as usize)).map(|p| p.to_string());
+                let mut swap_amount_in = None;
+                let is_swap = match decoded_inst.data {
+                    RaydiumAmmV4Instruction::SwapBaseIn(swap_data) => {
+                        println!("[SwapBaseIn] Signature: {}, amount_in: {}, min_out: {}, slot: {}", signature, swap_data.amount_in, swap_data.minimum_amount_out, slot);
+                        save_event(signature, swap_data.amount_in, swap_data.minimum_amount_out, slot, pool.as_deref(), fetched.block_time);
+                        ctx.finality.track(signature, slot);
+                        swap_amount_in = Some(swap_data.amount_in);
+                        true
+                    }
+                    RaydiumAmmV4Instruction::SwapBaseOut(_) => true,
+                    RaydiumAmmV4Instruction::Initialize2(_) => {
+                        created_pools.extend(PoolCreation::from_initialize2(cix, &account_keys, signature, slot, timestamp));
+                        false
+                    }
+                    _ => false,
+                };
+
+                if !is_swap {
+                    continue;
+                }
+
+                swapped_pools.extend(pool);
+
+                // Неудавшийся свап не меняет балансы хранилищ и затёр бы последнюю реальную сделку
+                if !succeeded {
+                    continue;
                 }
+
+                let pool_price = price::pool_price_from_swap(cix, &account_keys, &fetched.meta, slot, signature, timestamp);
+
+                if let Some(amount_in) = swap_amount_in {
+                    if let Some(sample) = SwapSample::from_swap_base_in(signature, slot, instruction_index, cix, &account_keys, amount_in, pool_price.as_ref()) {
+                        ctx.verifier.submit(sample);
+                    }
+                }
+
+                if let Some(pool_price) = pool_price {
+                    ctx.price_state.update(pool_price);
+                }
+            }
+        }
+    }
+
+    // В статистику комиссий попадают только успешно исполненные свапы
+    if !swapped_pools.is_empty() && succeeded {
+        // Мультихоп или арбитраж может пройти через один пул дважды
+        swapped_pools.sort();
+        swapped_pools.dedup();
+        ctx.fee_stats.record(&swapped_pools, fees::compute_unit_price(&versioned_tx.message));
+    }
+
+    // Поиск создания минта может ждать RPC (а в режиме Block — повторять), поэтому идёт вне задачи подписки
+    if succeeded {
+        for pool in created_pools {
+            println!("[Initialize2] Пул {} создан: {} / {}", pool.pool, pool.coin_mint, pool.pc_mint);
+            let lineage = ctx.lineage.clone();
+            tokio::spawn(async move { lineage.on_pool_created(&pool).await });
+        }
+    }
+}
+
+// Ключи транзакции вместе с адресами из lookup-таблиц (в порядке индексов meta)
+fn full_account_keys(msg: &VersionedMessage, meta: &Value) -> Vec<Pubkey> {
+    let mut keys = msg.static_account_keys().to_vec();
+    for kind in ["writable", "readonly"] {
+        for key in meta["loadedAddresses"][kind].as_array().into_iter().flatten() {
+            if let Some(key) = key.as_str().and_then(|k| Pubkey::from_str(k).ok()) {
+                keys.push(key);
             }
         }
     }
+    keys
 }
 
-// Преобразует `CompiledInstruction` в `Instruction`
+// Преобразует `CompiledInstruction` в `Instruction`; account_keys — из `full_account_keys`
 fn convert_compiled_instruction(
     cix: &CompiledInstruction,
     msg: &VersionedMessage,
+    account_keys: &[Pubkey],
 ) -> Option<Instruction> {
-    let account_keys: Vec<Pubkey> = msg.static_account_keys().to_vec();
     let program_id_index = cix.program_id_index as usize;
 
     if program_id_index >= account_keys.len() {
@@ -202,15 +437,18 @@ fn convert_compiled_instruction(
 
 
 // Сохранение `SwapBaseIn` в JSON
-fn save_event(signature: &str, amount_in: u64, min_out: u64, slot: u64) {
+fn save_event(signature: &str, amount_in: u64, min_out: u64, slot: u64, pool: Option<&str>, block_time: Option<u64>) {
     let event = serde_json::json!({
         "transaction_signature": signature,
         "slot": slot,
+        "block_time": block_time,
+        "pool": pool,
         "amount_in": amount_in,
-        "min_amount_out": min_out
+        "min_amount_out": min_out,
+        "commitment": CAPTURE_COMMITMENT
     });
 
-    let mut file = OpenOptions::new().create(true).append(true).open("swap_events.json").expect("Ошибка открытия файла");
+    let mut file = OpenOptions::new().create(true).append(true).open(SWAP_EVENTS_FILE).expect("Ошибка открытия файла");
     writeln!(file, "{}", event.to_string()).expect("Ошибка записи в файл");
-    println!("Событие сохранено в swap_events.json");
+    println!("Событие сохранено в {}", SWAP_EVENTS_FILE);
 }
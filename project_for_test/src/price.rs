@@ -0,0 +1,264 @@
+use serde_json::Value;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+// Через сколько секунд без сделок цена считается устаревшей
+pub const PRICE_STALE_SECS: u64 = 60;
+
+// Котируемые минты (WSOL, USDC, USDT): они есть в тысячах пулов, поэтому цену по ним не ищем
+pub const QUOTE_MINTS: [&str; 3] = [
+    "So11111111111111111111111111111111111111112",
+    "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+    "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
+];
+
+// Версия и длина бинарного кадра цены
+const PRICE_FRAME_VERSION: u8 = 1;
+pub const PRICE_FRAME_LEN: usize = 202;
+
+// Сколько обновлений цен держит поток подписчиков, прежде чем медленный подписчик начнёт их терять
+const PRICE_STREAM_CAPACITY: usize = 1024;
+
+// Количество аккаунтов в SwapBaseIn/SwapBaseOut без amm_target_orders
+const SWAP_ACCOUNTS_WITHOUT_TARGET_ORDERS: usize = 17;
+
+// Последняя известная цена пула (цена coin в единицах pc)
+#[derive(Clone, Debug)]
+pub struct PoolPrice {
+    pub pool: String,
+    pub coin_mint: String,
+    pub pc_mint: String,
+    pub last_trade_price: Option<f64>,
+    pub mid_price: Option<f64>,
+    pub slot: u64,
+    pub signature: String,
+    pub timestamp: u64,
+}
+
+impl PoolPrice {
+    pub fn to_json(&self, now: u64) -> Value {
+        let age_secs = now.saturating_sub(self.timestamp);
+        serde_json::json!({
+            "pool": self.pool,
+            "coin_mint": self.coin_mint,
+            "pc_mint": self.pc_mint,
+            "last_trade_price": self.last_trade_price,
+            "mid_price": self.mid_price,
+            "slot": self.slot,
+            "transaction_signature": self.signature,
+            "timestamp": self.timestamp,
+            "age_secs": age_secs,
+            "stale": age_secs > PRICE_STALE_SECS
+        })
+    }
+
+    // Компактный бинарный кадр фиксированной длины (202 байта, little-endian):
+    // version u8 | pool [32] | coin_mint [32] | pc_mint [32] | last_trade_price f64 | mid_price f64 |
+    // slot u64 | timestamp u64 | age_secs u64 | stale u8 | signature [64].
+    // Отсутствующая цена кодируется как NaN. None — если ключ или подпись не разбираются
+    pub fn to_frame(&self, now: u64) -> Option<Vec<u8>> {
+        let age_secs = now.saturating_sub(self.timestamp);
+        let mut frame = Vec::with_capacity(PRICE_FRAME_LEN);
+
+        frame.push(PRICE_FRAME_VERSION);
+        for key in [&self.pool, &self.coin_mint, &self.pc_mint] {
+            frame.extend_from_slice(&Pubkey::from_str(key).ok()?.to_bytes());
+        }
+        frame.extend_from_slice(&self.last_trade_price.unwrap_or(f64::NAN).to_le_bytes());
+        frame.extend_from_slice(&self.mid_price.unwrap_or(f64::NAN).to_le_bytes());
+        frame.extend_from_slice(&self.slot.to_le_bytes());
+        frame.extend_from_slice(&self.timestamp.to_le_bytes());
+        frame.extend_from_slice(&age_secs.to_le_bytes());
+        frame.push((age_secs > PRICE_STALE_SECS) as u8);
+        frame.extend_from_slice(Signature::from_str(&self.signature).ok()?.as_ref());
+
+        Some(frame)
+    }
+}
+
+// Цены в памяти: пул -> цена, минт -> последний пул, где он торговался.
+// Каждое обновление также рассылается подписчикам потока цен
+#[derive(Clone)]
+pub struct PriceState {
+    inner: Arc<RwLock<PriceStateInner>>,
+    updates: broadcast::Sender<PoolPrice>,
+}
+
+impl Default for PriceState {
+    fn default() -> Self {
+        let (updates, _) = broadcast::channel(PRICE_STREAM_CAPACITY);
+        PriceState { inner: Arc::default(), updates }
+    }
+}
+
+#[derive(Default)]
+struct PriceStateInner {
+    pools: HashMap<String, PoolPrice>,
+    mints: HashMap<String, String>,
+}
+
+impl PriceState {
+    pub fn update(&self, price: PoolPrice) {
+        let mut inner = self.inner.write().unwrap();
+        inner.mints.insert(price.coin_mint.clone(), price.pool.clone());
+        inner.mints.insert(price.pc_mint.clone(), price.pool.clone());
+        inner.pools.insert(price.pool.clone(), price.clone());
+        drop(inner);
+
+        // Ошибка означает лишь отсутствие подписчиков
+        let _ = self.updates.send(price);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PoolPrice> {
+        self.updates.subscribe()
+    }
+
+    // Ищет цену сначала по адресу пула, затем по минту. По минту возвращается пул,
+    // в котором этот минт торговался последним, — для котируемых минтов это был бы
+    // случайный пул, поэтому они отклоняются (см. `is_quote_mint`)
+    pub fn lookup(&self, mint_or_pool: &str) -> Option<PoolPrice> {
+        let inner = self.inner.read().unwrap();
+        if let Some(price) = inner.pools.get(mint_or_pool) {
+            return Some(price.clone());
+        }
+        if is_quote_mint(mint_or_pool) {
+            return None;
+        }
+        let pool = inner.mints.get(mint_or_pool)?;
+        inner.pools.get(pool).cloned()
+    }
+}
+
+// Индексы хранилищ пула (coin, pc) в инструкции свапа.
+// Хранилища идут сразу за amm_target_orders (или amm_open_orders, если его нет)
+pub fn pool_vault_accounts(cix: &CompiledInstruction) -> Option<(u8, u8)> {
+    if cix.accounts.len() < SWAP_ACCOUNTS_WITHOUT_TARGET_ORDERS {
+        return None;
+    }
+
+    let vault_offset = cix.accounts.len() - SWAP_ACCOUNTS_WITHOUT_TARGET_ORDERS;
+    Some((cix.accounts[4 + vault_offset], cix.accounts[5 + vault_offset]))
+}
+
+// Баланс токен-аккаунта из preTokenBalances/postTokenBalances
+struct VaultBalance {
+    mint: String,
+    ui_amount: f64,
+}
+
+fn vault_balance(balances: &Value, account_index: u8) -> Option<VaultBalance> {
+    let entry = balances.as_array()?.iter().find(|b| b["accountIndex"].as_u64() == Some(account_index as u64))?;
+    let amount: u64 = entry["uiTokenAmount"]["amount"].as_str()?.parse().ok()?;
+    let decimals = entry["uiTokenAmount"]["decimals"].as_u64()? as i32;
+
+    Some(VaultBalance {
+        mint: entry["mint"].as_str()?.to_string(),
+        ui_amount: amount as f64 / 10f64.powi(decimals),
+    })
+}
+
+// Считает цену пула по балансам хранилищ до и после свапа
+pub fn pool_price_from_swap(
+    cix: &CompiledInstruction,
+    account_keys: &[Pubkey],
+    meta: &Value,
+    slot: u64,
+    signature: &str,
+    timestamp: u64,
+) -> Option<PoolPrice> {
+    let (coin_vault, pc_vault) = pool_vault_accounts(cix)?;
+    let pool = account_keys.get(cix.accounts[1] as usize)?;
+
+    let coin_post = vault_balance(&meta["postTokenBalances"], coin_vault)?;
+    let pc_post = vault_balance(&meta["postTokenBalances"], pc_vault)?;
+
+    let mid_price = if coin_post.ui_amount > 0.0 {
+        Some(pc_post.ui_amount / coin_post.ui_amount)
+    } else {
+        None
+    };
+
+    let last_trade_price = match (
+        vault_balance(&meta["preTokenBalances"], coin_vault),
+        vault_balance(&meta["preTokenBalances"], pc_vault),
+    ) {
+        (Some(coin_pre), Some(pc_pre)) => {
+            let coin_delta = (coin_post.ui_amount - coin_pre.ui_amount).abs();
+            let pc_delta = (pc_post.ui_amount - pc_pre.ui_amount).abs();
+            if coin_delta > 0.0 { Some(pc_delta / coin_delta) } else { None }
+        }
+        _ => None,
+    };
+
+    Some(PoolPrice {
+        pool: pool.to_string(),
+        coin_mint: coin_post.mint,
+        pc_mint: pc_post.mint,
+        last_trade_price,
+        mid_price,
+        slot,
+        signature: signature.to_string(),
+        timestamp,
+    })
+}
+
+pub fn is_quote_mint(mint: &str) -> bool {
+    QUOTE_MINTS.contains(&mint)
+}
+
+pub fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_price() -> PoolPrice {
+        PoolPrice {
+            pool: Pubkey::new_from_array([1; 32]).to_string(),
+            coin_mint: Pubkey::new_from_array([2; 32]).to_string(),
+            pc_mint: Pubkey::new_from_array([3; 32]).to_string(),
+            last_trade_price: Some(1.5),
+            mid_price: None,
+            slot: 319022508,
+            signature: Signature::from([4; 64]).to_string(),
+            timestamp: 1_000,
+        }
+    }
+
+    #[test]
+    fn frame_layout_matches_documented_offsets() {
+        let frame = sample_price().to_frame(1_000 + PRICE_STALE_SECS + 1).unwrap();
+
+        assert_eq!(frame.len(), PRICE_FRAME_LEN);
+        assert_eq!(frame[0], PRICE_FRAME_VERSION);
+        assert_eq!(&frame[1..33], &[1; 32]);
+        assert_eq!(&frame[33..65], &[2; 32]);
+        assert_eq!(&frame[65..97], &[3; 32]);
+        assert_eq!(f64::from_le_bytes(frame[97..105].try_into().unwrap()), 1.5);
+        assert!(f64::from_le_bytes(frame[105..113].try_into().unwrap()).is_nan());
+        assert_eq!(u64::from_le_bytes(frame[113..121].try_into().unwrap()), 319022508);
+        assert_eq!(u64::from_le_bytes(frame[121..129].try_into().unwrap()), 1_000);
+        assert_eq!(u64::from_le_bytes(frame[129..137].try_into().unwrap()), PRICE_STALE_SECS + 1);
+        assert_eq!(frame[137], 1);
+        assert_eq!(&frame[138..202], &[4; 64]);
+    }
+
+    #[test]
+    fn frame_is_not_built_from_unparsable_keys() {
+        let mut price = sample_price();
+        price.coin_mint = "not-a-pubkey".to_string();
+        assert!(price.to_frame(1_000).is_none());
+
+        let mut price = sample_price();
+        price.signature = "not-a-signature".to_string();
+        assert!(price.to_frame(1_000).is_none());
+    }
+}
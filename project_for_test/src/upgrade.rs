@@ -0,0 +1,114 @@
+use reqwest::Client;
+use serde_json::Value;
+use solana_sdk::bpf_loader_upgradeable;
+use solana_sdk::pubkey::Pubkey;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::price::now_unix;
+use crate::watchdog::Heartbeats;
+use crate::{RAYDIUM_PROGRAM_ID, RPC_HTTP_URL};
+
+// Как часто проверять programdata Raydium
+const UPGRADE_POLL_SECS: u64 = 30;
+const ALERTS_FILE: &str = "alerts.json";
+// Последний увиденный слот деплоя переживает перезапуски процесса и стадии
+const DEPLOY_SLOT_FILE: &str = "program_deploy_slot.json";
+
+// Слот последнего деплоя лежит в programdata сразу после 4-байтного тега
+const PROGRAMDATA_SLOT_OFFSET: usize = 4;
+
+// Следит за programdata программы Raydium и поднимает алерт при апгрейде
+pub async fn watch_program_upgrades(heartbeats: Heartbeats) {
+    let program_id = Pubkey::from_str(RAYDIUM_PROGRAM_ID).unwrap();
+    let programdata = bpf_loader_upgradeable::get_program_data_address(&program_id);
+    let client = Client::new();
+    let mut known_slot = load_known_deploy_slot(&program_id);
+
+    println!("Отслеживаем апгрейды {} (programdata {})", program_id, programdata);
+
+    loop {
+        match fetch_last_deploy_slot(&client, &programdata).await {
+            Some(slot) => {
+                match known_slot {
+                    None => println!("Программа {} задеплоена в слоте {}", program_id, slot),
+                    Some(previous) if previous != slot => {
+                        println!("ВНИМАНИЕ: программа {} обновлена (слот {} -> {}), декодер может ошибаться", program_id, previous, slot);
+                        save_upgrade_alert(&program_id, &programdata, previous, slot);
+                    }
+                    _ => {}
+                }
+                // Состояние пишем после алерта: при падении между ними алерт повторится, а не потеряется
+                if known_slot != Some(slot) {
+                    save_known_deploy_slot(&program_id, slot);
+                }
+                known_slot = Some(slot);
+            }
+            None => println!("Не удалось получить programdata {}", programdata),
+        }
+
+        heartbeats.beat("upgrade_watch");
+        tokio::time::sleep(Duration::from_secs(UPGRADE_POLL_SECS)).await;
+    }
+}
+
+// Читает из programdata только слот последнего деплоя
+async fn fetch_last_deploy_slot(client: &Client, programdata: &Pubkey) -> Option<u64> {
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getAccountInfo",
+        "params": [
+            programdata.to_string(),
+            { "encoding": "base64", "commitment": "confirmed", "dataSlice": { "offset": PROGRAMDATA_SLOT_OFFSET, "length": 8 } }
+        ]
+    });
+
+    let response = client.post(RPC_HTTP_URL).json(&request_body).send().await.ok()?;
+    let json_resp: Value = response.json().await.ok()?;
+
+    let base64_str = json_resp["result"]["value"]["data"][0].as_str()?;
+    let bytes = base64::decode(base64_str).ok()?;
+    let slot_bytes: [u8; 8] = bytes.get(..8)?.try_into().ok()?;
+
+    Some(u64::from_le_bytes(slot_bytes))
+}
+
+fn load_known_deploy_slot(program_id: &Pubkey) -> Option<u64> {
+    let raw = fs::read_to_string(DEPLOY_SLOT_FILE).ok()?;
+    let state: Value = serde_json::from_str(&raw).ok()?;
+    if state["program_id"].as_str() != Some(program_id.to_string().as_str()) {
+        return None;
+    }
+    state["deploy_slot"].as_u64()
+}
+
+fn save_known_deploy_slot(program_id: &Pubkey, slot: u64) {
+    let state = serde_json::json!({
+        "program_id": program_id.to_string(),
+        "deploy_slot": slot
+    });
+
+    // Через временный файл, чтобы не оставить обрезанное состояние
+    let tmp_path = format!("{}.tmp", DEPLOY_SLOT_FILE);
+    if let Err(e) = fs::write(&tmp_path, state.to_string()).and_then(|_| fs::rename(&tmp_path, DEPLOY_SLOT_FILE)) {
+        println!("Ошибка сохранения {}: {:?}", DEPLOY_SLOT_FILE, e);
+    }
+}
+
+// Сохранение алерта об апгрейде в JSON
+fn save_upgrade_alert(program_id: &Pubkey, programdata: &Pubkey, previous_slot: u64, new_slot: u64) {
+    let event = serde_json::json!({
+        "event": "ProgramUpgraded",
+        "program_id": program_id.to_string(),
+        "programdata": programdata.to_string(),
+        "previous_deploy_slot": previous_slot,
+        "new_deploy_slot": new_slot,
+        "detected_at": now_unix()
+    });
+
+    let mut file = OpenOptions::new().create(true).append(true).open(ALERTS_FILE).expect("Ошибка открытия файла");
+    writeln!(file, "{}", event).expect("Ошибка записи в файл");
+}
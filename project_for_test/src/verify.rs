@@ -0,0 +1,302 @@
+use reqwest::Client;
+use serde_json::Value;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::pubkey::Pubkey;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::cache::TxCache;
+use crate::price::{self, PoolPrice};
+use crate::watchdog::{self, StageExit};
+
+// Эталонный RPC/эксплорер для сверки (jsonParsed). Пустая строка — сверка отключена
+const VERIFY_RPC_URL: &str = "";
+// Сверяем каждый N-й декодированный SwapBaseIn
+const VERIFY_SAMPLE_EVERY: u64 = 20;
+// Очередь на сверку ограничена: при медленном эталоне лишние образцы отбрасываются
+const VERIFY_QUEUE_CAPACITY: usize = 100;
+const VERIFY_TIMEOUT_SECS: u64 = 10;
+const VERIFY_MISMATCHES_FILE: &str = "verify_mismatches.json";
+
+// Декодированный свап, отобранный для сверки
+pub struct SwapSample {
+    signature: String,
+    slot: u64,
+    instruction_index: usize,
+    amount_in: u64,
+    user_source: String,
+    coin_vault: String,
+    pc_vault: String,
+    coin_mint: Option<String>,
+    pc_mint: Option<String>,
+}
+
+impl SwapSample {
+    // Собирает образец из инструкции SwapBaseIn; user_source — третий аккаунт с конца
+    pub fn from_swap_base_in(
+        signature: &str,
+        slot: u64,
+        instruction_index: usize,
+        cix: &CompiledInstruction,
+        account_keys: &[Pubkey],
+        amount_in: u64,
+        pool_price: Option<&PoolPrice>,
+    ) -> Option<Self> {
+        let (coin_vault, pc_vault) = price::pool_vault_accounts(cix)?;
+        let user_source = cix.accounts[cix.accounts.len() - 3];
+
+        Some(SwapSample {
+            signature: signature.to_string(),
+            slot,
+            instruction_index,
+            amount_in,
+            user_source: account_keys.get(user_source as usize)?.to_string(),
+            coin_vault: account_keys.get(coin_vault as usize)?.to_string(),
+            pc_vault: account_keys.get(pc_vault as usize)?.to_string(),
+            coin_mint: pool_price.map(|p| p.coin_mint.clone()),
+            pc_mint: pool_price.map(|p| p.pc_mint.clone()),
+        })
+    }
+}
+
+// Фоновая сверка декодированных свапов с эталонным API.
+// Очередь пересоздаётся при каждом запуске воркера, поэтому супервизор может его перезапускать
+#[derive(Clone, Default)]
+pub struct Verifier {
+    sender: Arc<Mutex<Option<mpsc::Sender<SwapSample>>>>,
+    seen: Arc<AtomicU64>,
+}
+
+impl Verifier {
+    pub fn is_enabled() -> bool {
+        !VERIFY_RPC_URL.is_empty()
+    }
+
+    // Запускает воркер сверки; образцы, стоявшие в очереди прежнего воркера, теряются
+    pub fn spawn_worker(&self, tx_cache: TxCache) -> JoinHandle<StageExit> {
+        let (sender, receiver) = mpsc::channel(VERIFY_QUEUE_CAPACITY);
+        *self.sender.lock().unwrap() = Some(sender);
+        println!("Сверка с эталонным API включена (каждый {}-й свап)", VERIFY_SAMPLE_EVERY);
+
+        watchdog::spawn_background(run_verifier(receiver, tx_cache))
+    }
+
+    pub fn submit(&self, sample: SwapSample) {
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            if self.seen.fetch_add(1, Ordering::Relaxed) % VERIFY_SAMPLE_EVERY == 0 {
+                if let Err(mpsc::error::TrySendError::Full(sample)) = sender.try_send(sample) {
+                    println!("Сверка: очередь заполнена, образец {} отброшен", sample.signature);
+                }
+            }
+        }
+    }
+}
+
+async fn run_verifier(mut receiver: mpsc::Receiver<SwapSample>, tx_cache: TxCache) {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(VERIFY_TIMEOUT_SECS))
+        .build()
+        .expect("Ошибка создания HTTP-клиента");
+
+    while let Some(sample) = receiver.recv().await {
+        let reference = match tx_cache.get(&sample.signature, "jsonParsed") {
+            Some(reference) => Some(reference),
+            None => fetch_reference(&client, &sample.signature).await,
+        };
+
+        let reference = match reference {
+            Some(reference) => reference,
+            None => {
+                println!("Сверка: эталон не вернул транзакцию {}", sample.signature);
+                continue;
+            }
+        };
+        tx_cache.put(&sample.signature, "jsonParsed", &reference);
+
+        let mismatches = compare_with_reference(&sample, &reference);
+        if mismatches.is_empty() {
+            println!("Сверка: {} совпадает с эталоном", sample.signature);
+        } else {
+            println!("Сверка: {} расходится с эталоном: {:?}", sample.signature, mismatches);
+            save_mismatch(&sample, &mismatches);
+        }
+    }
+}
+
+// Запрашивает у эталона ту же транзакцию в jsonParsed
+async fn fetch_reference(client: &Client, signature: &str) -> Option<Value> {
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getTransaction",
+        "params": [
+            signature,
+            { "encoding": "jsonParsed", "commitment": "confirmed", "maxSupportedTransactionVersion": 0 }
+        ]
+    });
+
+    let response = client.post(VERIFY_RPC_URL).json(&request_body).send().await.ok()?;
+    let json_resp: Value = response.json().await.ok()?;
+    if json_resp["result"].is_null() {
+        return None;
+    }
+
+    Some(json_resp["result"].clone())
+}
+
+// Сравнивает amount_in, исходный аккаунт и минты пула с эталоном
+fn compare_with_reference(sample: &SwapSample, reference: &Value) -> Vec<String> {
+    let mut mismatches = Vec::new();
+
+    // Первый токен-трансфер внутри свапа — перевод amount_in от пользователя в хранилище пула
+    let transfer = reference["meta"]["innerInstructions"]
+        .as_array()
+        .and_then(|inner| inner.iter().find(|i| i["index"].as_u64() == Some(sample.instruction_index as u64)))
+        .and_then(|inner| inner["instructions"].as_array())
+        .and_then(|ixs| {
+            ixs.iter().find(|ix| {
+                matches!(ix["parsed"]["type"].as_str(), Some("transfer") | Some("transferChecked"))
+            })
+        });
+
+    match transfer {
+        Some(transfer) => {
+            let info = &transfer["parsed"]["info"];
+            let amount = info["amount"]
+                .as_str()
+                .or_else(|| info["tokenAmount"]["amount"].as_str())
+                .and_then(|a| a.parse::<u64>().ok());
+
+            if amount != Some(sample.amount_in) {
+                mismatches.push(format!("amount_in: декодировано {}, эталон {:?}", sample.amount_in, amount));
+            }
+            if info["source"].as_str() != Some(sample.user_source.as_str()) {
+                mismatches.push(format!("user_source: декодировано {}, эталон {:?}", sample.user_source, info["source"].as_str()));
+            }
+        }
+        None => mismatches.push("в эталоне нет токен-трансфера для инструкции свапа".to_string()),
+    }
+
+    for (vault, mint) in [(&sample.coin_vault, &sample.coin_mint), (&sample.pc_vault, &sample.pc_mint)] {
+        let Some(mint) = mint else { continue };
+        let reference_mint = reference_vault_mint(reference, vault);
+        if reference_mint.as_deref() != Some(mint.as_str()) {
+            mismatches.push(format!("mint хранилища {}: декодировано {}, эталон {:?}", vault, mint, reference_mint));
+        }
+    }
+
+    mismatches
+}
+
+fn reference_vault_mint(reference: &Value, vault: &str) -> Option<String> {
+    let account_index = reference["transaction"]["message"]["accountKeys"]
+        .as_array()?
+        .iter()
+        .position(|key| key["pubkey"].as_str() == Some(vault))?;
+
+    reference["meta"]["postTokenBalances"]
+        .as_array()?
+        .iter()
+        .find(|b| b["accountIndex"].as_u64() == Some(account_index as u64))
+        .and_then(|b| b["mint"].as_str())
+        .map(|m| m.to_string())
+}
+
+// Сохранение расхождений в JSON
+fn save_mismatch(sample: &SwapSample, mismatches: &[String]) {
+    let event = serde_json::json!({
+        "transaction_signature": sample.signature,
+        "slot": sample.slot,
+        "instruction_index": sample.instruction_index,
+        "amount_in": sample.amount_in,
+        "mismatches": mismatches
+    });
+
+    let mut file = OpenOptions::new().create(true).append(true).open(VERIFY_MISMATCHES_FILE).expect("Ошибка открытия файла");
+    writeln!(file, "{}", event).expect("Ошибка записи в файл");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SwapSample {
+        SwapSample {
+            signature: "sig".to_string(),
+            slot: 1,
+            instruction_index: 2,
+            amount_in: 1_000,
+            user_source: "user_source".to_string(),
+            coin_vault: "coin_vault".to_string(),
+            pc_vault: "pc_vault".to_string(),
+            coin_mint: Some("coin_mint".to_string()),
+            pc_mint: Some("pc_mint".to_string()),
+        }
+    }
+
+    // Минимальный jsonParsed-ответ: один transfer во внутренних инструкциях свапа и балансы хранилищ
+    fn reference(amount: &str, source: &str, transfer_index: u64) -> Value {
+        serde_json::json!({
+            "transaction": { "message": { "accountKeys": [
+                { "pubkey": "user_source" },
+                { "pubkey": "coin_vault" },
+                { "pubkey": "pc_vault" }
+            ] } },
+            "meta": {
+                "innerInstructions": [{
+                    "index": transfer_index,
+                    "instructions": [{
+                        "parsed": { "type": "transfer", "info": { "amount": amount, "source": source } }
+                    }]
+                }],
+                "postTokenBalances": [
+                    { "accountIndex": 1, "mint": "coin_mint" },
+                    { "accountIndex": 2, "mint": "pc_mint" }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn matching_reference_has_no_mismatches() {
+        assert!(compare_with_reference(&sample(), &reference("1000", "user_source", 2)).is_empty());
+    }
+
+    #[test]
+    fn wrong_amount_is_reported() {
+        let mismatches = compare_with_reference(&sample(), &reference("999", "user_source", 2));
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].starts_with("amount_in"));
+    }
+
+    #[test]
+    fn wrong_source_is_reported() {
+        let mismatches = compare_with_reference(&sample(), &reference("1000", "someone_else", 2));
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].starts_with("user_source"));
+    }
+
+    #[test]
+    fn missing_inner_transfer_is_reported() {
+        let mismatches = compare_with_reference(&sample(), &reference("1000", "user_source", 0));
+        assert_eq!(mismatches, vec!["в эталоне нет токен-трансфера для инструкции свапа".to_string()]);
+    }
+
+    #[test]
+    fn vault_mint_is_read_from_post_token_balances() {
+        let reference = reference("1000", "user_source", 2);
+        assert_eq!(reference_vault_mint(&reference, "pc_vault").as_deref(), Some("pc_mint"));
+        assert_eq!(reference_vault_mint(&reference, "unknown_vault"), None);
+
+        let mut sample = sample();
+        sample.coin_mint = Some("other_mint".to_string());
+        let mismatches = compare_with_reference(&sample, &reference);
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].starts_with("mint хранилища coin_vault"));
+    }
+}
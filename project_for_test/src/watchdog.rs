@@ -0,0 +1,170 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::price::now_unix;
+
+// Как часто супервизор проверяет стадии
+const WATCHDOG_CHECK_SECS: u64 = 5;
+// Пауза перед перезапуском удваивается с каждым сбоем подряд, от одной проверки до пяти минут
+const RESTART_BACKOFF_MAX_SECS: u64 = 300;
+// Стадия, проработавшая столько без сбоя, снова перезапускается без задержки
+const RESTART_RESET_SECS: u64 = 300;
+// После стольких сбоев основной стадии подряд программа завершается
+const PRIMARY_MAX_FAILURES: u32 = 10;
+
+// Пульс стадий конвейера и счётчики перезапусков
+#[derive(Clone, Default)]
+pub struct Heartbeats {
+    inner: Arc<Mutex<HashMap<&'static str, StageHealth>>>,
+}
+
+#[derive(Default)]
+struct StageHealth {
+    // Стадии без пульса (stall_secs = None) только считают перезапуски
+    beats: bool,
+    last_beat: u64,
+    restarts: u64,
+}
+
+impl Heartbeats {
+    fn register(&self, stage: &'static str, beats: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        let health = inner.entry(stage).or_default();
+        health.beats = beats;
+        health.last_beat = now_unix();
+    }
+
+    pub fn beat(&self, stage: &'static str) {
+        self.inner.lock().unwrap().entry(stage).or_default().last_beat = now_unix();
+    }
+
+    fn silent_secs(&self, stage: &'static str) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        inner.get(stage).map(|h| now_unix().saturating_sub(h.last_beat)).unwrap_or(0)
+    }
+
+    fn record_restart(&self, stage: &'static str) {
+        let mut inner = self.inner.lock().unwrap();
+        let health = inner.entry(stage).or_default();
+        health.restarts += 1;
+        health.last_beat = now_unix();
+    }
+
+    pub fn to_json(&self) -> Value {
+        let now = now_unix();
+        let inner = self.inner.lock().unwrap();
+        let stages: serde_json::Map<String, Value> = inner
+            .iter()
+            .map(|(stage, health)| {
+                let mut fields = serde_json::json!({ "restarts": health.restarts });
+                if health.beats {
+                    fields["silent_secs"] = Value::from(now.saturating_sub(health.last_beat));
+                }
+                (stage.to_string(), fields)
+            })
+            .collect();
+
+        serde_json::json!({ "stages": stages })
+    }
+}
+
+// Чем закончилась стадия: Finished — штатно выполнила свою работу, Failed — оборвалась и её нужно поднять
+pub enum StageExit {
+    Finished,
+    Failed,
+}
+
+// Запуск бесконечной фоновой стадии: её возврат всегда означает сбой
+pub fn spawn_background(stage: impl Future<Output = ()> + Send + 'static) -> JoinHandle<StageExit> {
+    tokio::spawn(async move {
+        stage.await;
+        StageExit::Failed
+    })
+}
+
+// Стадия под надзором: задача, способ её перезапустить и допустимое время без пульса
+struct Stage {
+    name: &'static str,
+    stall_secs: Option<u64>,
+    spawn: Box<dyn Fn() -> JoinHandle<StageExit> + Send>,
+    handle: JoinHandle<StageExit>,
+    started_at: u64,
+    // Сбои подряд и время отложенного перезапуска
+    failures: u32,
+    restart_at: Option<u64>,
+}
+
+// Супервизор перезапускает стадии, которые замолчали или тихо завершились
+pub struct Supervisor {
+    heartbeats: Heartbeats,
+    stages: Vec<Stage>,
+}
+
+impl Supervisor {
+    pub fn new(heartbeats: Heartbeats) -> Self {
+        Supervisor { heartbeats, stages: Vec::new() }
+    }
+
+    // stall_secs = None — стадия без пульса, проверяется только её завершение
+    pub fn add(&mut self, name: &'static str, stall_secs: Option<u64>, spawn: impl Fn() -> JoinHandle<StageExit> + Send + 'static) {
+        self.heartbeats.register(name, stall_secs.is_some());
+        let handle = spawn();
+        self.stages.push(Stage { name, stall_secs, spawn: Box::new(spawn), handle, started_at: now_unix(), failures: 0, restart_at: None });
+    }
+
+    // Работает, пока стадия `primary` не вернёт StageExit::Finished; сбой, паника или зависание — перезапуск
+    // с нарастающей паузой. Failed — основная стадия сбоила PRIMARY_MAX_FAILURES раз подряд
+    pub async fn run_until_finished(mut self, primary: &'static str) -> StageExit {
+        loop {
+            tokio::time::sleep(Duration::from_secs(WATCHDOG_CHECK_SECS)).await;
+
+            for stage in self.stages.iter_mut() {
+                let now = now_unix();
+                if let Some(restart_at) = stage.restart_at {
+                    if now < restart_at {
+                        continue;
+                    }
+                    stage.restart_at = None;
+                    self.heartbeats.record_restart(stage.name);
+                    stage.handle = (stage.spawn)();
+                    stage.started_at = now;
+                    continue;
+                }
+
+                if stage.handle.is_finished() {
+                    let finished = matches!((&mut stage.handle).await, Ok(StageExit::Finished));
+                    if stage.name == primary && finished {
+                        return StageExit::Finished;
+                    }
+                    println!("Watchdog: стадия {} завершилась, перезапускаем", stage.name);
+                } else if let Some(stall_secs) = stage.stall_secs {
+                    let silent_secs = self.heartbeats.silent_secs(stage.name);
+                    if silent_secs <= stall_secs {
+                        continue;
+                    }
+                    println!("Watchdog: стадия {} молчит {} с, перезапускаем", stage.name, silent_secs);
+                    stage.handle.abort();
+                } else {
+                    continue;
+                }
+
+                if now.saturating_sub(stage.started_at) >= RESTART_RESET_SECS {
+                    stage.failures = 0;
+                }
+                stage.failures += 1;
+                if stage.name == primary && stage.failures >= PRIMARY_MAX_FAILURES {
+                    println!("Watchdog: стадия {} сбоила {} раз подряд, останавливаемся", stage.name, stage.failures);
+                    return StageExit::Failed;
+                }
+
+                let backoff = (WATCHDOG_CHECK_SECS << (stage.failures - 1).min(10)).min(RESTART_BACKOFF_MAX_SECS);
+                println!("Watchdog: перезапуск {} через {} с", stage.name, backoff);
+                stage.restart_at = Some(now + backoff);
+            }
+        }
+    }
+}